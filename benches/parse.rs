@@ -0,0 +1,59 @@
+// compares parsing a project with the full LDtk model against the trimmed runtime view
+//
+// there is no project checked into the repository, so point the benchmark at one:
+//   CARTOGRAPHER_BENCH_PROJECT=path/to/project.ldtk cargo bench --bench parse
+
+use std::{
+    env, fs,
+    time::{Duration, Instant},
+};
+
+use cartographer::{compat, ldtk::LdtkJson, runtime::Project};
+
+fn main() {
+    let path = match env::var_os("CARTOGRAPHER_BENCH_PROJECT") {
+        Some(path) => path,
+        None => {
+            eprintln!("CARTOGRAPHER_BENCH_PROJECT is not set, skipping");
+            return;
+        }
+    };
+    let iterations = env::var("CARTOGRAPHER_BENCH_ITERATIONS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(20);
+
+    let raw = fs::read_to_string(&path).expect("failed to read project");
+    println!("{} bytes, {} iterations", raw.len(), iterations);
+
+    let full = bench("full model", iterations, || {
+        let project: LdtkJson = compat::parse_project(&raw).unwrap();
+        project.levels.len()
+    });
+    let runtime = bench("runtime view", iterations, || {
+        let project: Project = compat::parse_project(&raw).unwrap();
+        project.levels.len()
+    });
+
+    println!(
+        "runtime view is {:.2}x faster",
+        full.as_secs_f64() / runtime.as_secs_f64()
+    );
+}
+
+/// Runs `f` once to warm up, then returns the mean time of `iterations` runs.
+fn bench(name: &str, iterations: u32, f: impl Fn() -> usize) -> Duration {
+    let levels = f();
+
+    // summing the returned counts keeps the parse from being optimized away
+    let mut total = 0;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        total += f();
+    }
+    let mean = start.elapsed() / iterations.max(1);
+
+    assert_eq!(total, levels * iterations as usize);
+    println!("{:>12}: {:?} per parse ({} levels)", name, mean, levels);
+    mean
+}
@@ -0,0 +1,350 @@
+// packs every level of a world into a single file, with an index up front recording the
+// identifier, iid, world position and size of each level along with where its LVL file is
+// stored, so a game ships one file per world and loads a single level by name
+
+use std::{
+    convert::TryFrom,
+    fs,
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use {
+    anyhow::{Context, Result},
+    byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt},
+};
+
+use crate::{
+    batch, compat, convert,
+    error::ReadError,
+    info,
+    reader::{self, Reader},
+    runtime::{self, Level, Project},
+    string::{self, StringTable},
+    world,
+};
+
+/// Magic bytes every archive starts with.
+pub const HEADER: &[u8] = b"LVL Archive 0.";
+
+/// File extension of archives.
+pub const EXTENSION: &str = "lva";
+
+/// Size of a single index entry.
+pub const ENTRY_SIZE: u64 = 4 * 9;
+
+/// A level of an archive, as recorded in its index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub identifier: String,
+    pub iid: String,
+
+    /// World coordinates of the top left corner of the level, in pixels
+    pub world_x: i32,
+    pub world_y: i32,
+    pub world_depth: i32,
+
+    /// Size of the level in pixels
+    pub width: u32,
+    pub height: u32,
+
+    /// Offset of the LVL file from the start of the archive
+    pub offset: u32,
+
+    /// Length of the LVL file in bytes
+    pub length: u32,
+}
+
+/// Writes an archive of already converted levels, in the given order.
+pub fn write<W: Write>(f: &mut W, world: &str, levels: &[(&Level, Vec<u8>)]) -> io::Result<()> {
+    let out_of_range = |field: &str, level: &Level| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "`{}` of level `{}` is out of range",
+                field, level.identifier
+            ),
+        )
+    };
+    let too_large = || io::Error::new(io::ErrorKind::InvalidData, "archive is too large");
+
+    let mut strings = StringTable::new();
+    let world = strings.insert(world)?;
+    let names = levels
+        .iter()
+        .map(|(level, _)| {
+            Ok((
+                strings.insert(&level.identifier)?,
+                strings.insert(&level.iid)?,
+            ))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    let mut table = Vec::new();
+    string::table(&mut table, &strings)?;
+
+    f.write_all(HEADER)?;
+    f.write_u32::<LittleEndian>(0)?; // flags
+    f.write_all(&table)?; // identifiers and iids
+    f.write_u32::<LittleEndian>(world)?; // world name
+    f.write_u32::<LittleEndian>(u32::try_from(levels.len()).map_err(|_| too_large())?)?; // level count
+
+    let mut offset =
+        (HEADER.len() + 4 + table.len() + 4 + 4) as u64 + ENTRY_SIZE * levels.len() as u64;
+    for ((level, data), (identifier, iid)) in levels.iter().zip(names) {
+        let length = data.len() as u64;
+        let int = |field, value| i32::try_from(value).map_err(|_| out_of_range(field, level));
+        let uint = |field, value| u32::try_from(value).map_err(|_| out_of_range(field, level));
+        f.write_u32::<LittleEndian>(identifier)?; // identifier
+        f.write_u32::<LittleEndian>(iid)?; // iid
+        f.write_i32::<LittleEndian>(int("worldX", level.world_x)?)?; // world x
+        f.write_i32::<LittleEndian>(int("worldY", level.world_y)?)?; // world y
+        f.write_i32::<LittleEndian>(int("worldDepth", level.world_depth)?)?; // world depth
+        f.write_u32::<LittleEndian>(uint("pxWid", level.px_wid)?)?; // width
+        f.write_u32::<LittleEndian>(uint("pxHei", level.px_hei)?)?; // height
+        f.write_u32::<LittleEndian>(u32::try_from(offset).map_err(|_| too_large())?)?; // offset
+        f.write_u32::<LittleEndian>(u32::try_from(length).map_err(|_| too_large())?)?; // length
+        offset += length;
+    }
+
+    for (_, data) in levels {
+        f.write_all(data)?;
+    }
+    Ok(())
+}
+
+/// Converts every level of a project into one archive per world in `output_dir`, named after
+/// the world. Levels at the root of the project go into an archive named after the project
+/// file. Returns the path of each archive written.
+pub fn run(
+    project_path: &Path,
+    info_table_paths: &[PathBuf],
+    output_dir: &Path,
+    options: &convert::Options,
+) -> Result<Vec<PathBuf>> {
+    let raw = fs::read_to_string(project_path)
+        .with_context(|| format!("failed to read {}", project_path.display()))?;
+    let project: Project = compat::parse_project(&raw)?;
+    let tables = info::read_all(info_table_paths)?;
+
+    fs::create_dir_all(output_dir)
+        .with_context(|| format!("failed to create {}", output_dir.display()))?;
+
+    let mut paths = Vec::new();
+    for world in world::worlds(project_path, &project) {
+        let mut converted = Vec::new();
+        for level in world.levels.iter() {
+            let map = runtime::load_level(project_path, &project, level)?;
+            let mut data = Vec::new();
+            convert::level(&project, &map, &tables, options, &mut data)?;
+            converted.push((level, data));
+        }
+
+        let path = output_dir.join(format!("{}.{}", world.identifier, EXTENSION));
+        batch::write_atomic(&path, |mut dst| {
+            write(&mut dst, &world.identifier, &converted)
+                .with_context(|| format!("failed to write {}", path.display()))
+        })?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Reads the index of an archive, then single levels out of it on demand.
+pub struct Archive<R> {
+    inner: R,
+    world: String,
+    entries: Vec<Entry>,
+}
+
+impl<R: Read + Seek> Archive<R> {
+    /// Checks the header and reads the index, leaving the levels themselves on disk.
+    pub fn new(mut inner: R) -> Result<Self, ReadError> {
+        let mut header = [0; HEADER.len()];
+        inner
+            .read_exact(&mut header)
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::UnexpectedEof => ReadError::BadHeader,
+                _ => ReadError::Io(err),
+            })?;
+        if header != HEADER {
+            return Err(ReadError::BadHeader);
+        }
+
+        let mut offset = HEADER.len() as u64;
+        let truncated = |offset| {
+            move |err: io::Error| match err.kind() {
+                io::ErrorKind::UnexpectedEof => ReadError::Truncated { offset },
+                _ => ReadError::Io(err),
+            }
+        };
+        let flags = inner
+            .read_u32::<LittleEndian>()
+            .map_err(truncated(offset))?;
+        if flags != 0 {
+            return Err(ReadError::UnsupportedFlags(flags));
+        }
+        offset += 4;
+
+        let code = inner.read_u8().map_err(truncated(offset))?;
+        if code != 0x6 {
+            return Err(ReadError::UnknownCommand { code, offset });
+        }
+        let (strings, len) = reader::read_strings(&mut inner, offset)?;
+        offset += 1 + len;
+        let string = |index: u32| {
+            strings
+                .get(index as usize)
+                .cloned()
+                .ok_or(ReadError::MissingString(index))
+        };
+
+        let world = string(
+            inner
+                .read_u32::<LittleEndian>()
+                .map_err(truncated(offset))?,
+        )?;
+        let count = inner
+            .read_u32::<LittleEndian>()
+            .map_err(truncated(offset))?;
+        offset += 8;
+
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let f = &mut inner;
+            let identifier = f.read_u32::<LittleEndian>().map_err(truncated(offset))?;
+            let iid = f.read_u32::<LittleEndian>().map_err(truncated(offset))?;
+            entries.push(Entry {
+                identifier: string(identifier)?,
+                iid: string(iid)?,
+                world_x: f.read_i32::<LittleEndian>().map_err(truncated(offset))?,
+                world_y: f.read_i32::<LittleEndian>().map_err(truncated(offset))?,
+                world_depth: f.read_i32::<LittleEndian>().map_err(truncated(offset))?,
+                width: f.read_u32::<LittleEndian>().map_err(truncated(offset))?,
+                height: f.read_u32::<LittleEndian>().map_err(truncated(offset))?,
+                offset: f.read_u32::<LittleEndian>().map_err(truncated(offset))?,
+                length: f.read_u32::<LittleEndian>().map_err(truncated(offset))?,
+            });
+            offset += ENTRY_SIZE;
+        }
+
+        Ok(Archive {
+            inner,
+            world,
+            entries,
+        })
+    }
+
+    /// Name of the world the levels belong to.
+    pub fn world(&self) -> &str {
+        &self.world
+    }
+
+    /// Every level of the archive, in project order.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Looks a level up by identifier or iid.
+    pub fn find(&self, name: &str) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.identifier == name || entry.iid == name)
+    }
+
+    /// Reads the LVL file of a level, looked up by identifier or iid.
+    pub fn read_level(&mut self, name: &str) -> Result<Vec<u8>, ReadError> {
+        let entry = self
+            .find(name)
+            .ok_or_else(|| ReadError::MissingLevel(name.to_owned()))?;
+        let (offset, length) = (u64::from(entry.offset), u64::from(entry.length));
+
+        self.inner
+            .seek(SeekFrom::Start(offset))
+            .map_err(ReadError::Io)?;
+        let mut data = Vec::new();
+        self.inner
+            .by_ref()
+            .take(length)
+            .read_to_end(&mut data)
+            .map_err(ReadError::Io)?;
+        if data.len() as u64 != length {
+            return Err(ReadError::Truncated { offset });
+        }
+        Ok(data)
+    }
+
+    /// Opens a level, looked up by identifier or iid, for reading its commands.
+    pub fn open_level(&mut self, name: &str) -> Result<Reader<Cursor<Vec<u8>>>, ReadError> {
+        Reader::new(Cursor::new(self.read_level(name)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::fixtures;
+
+    fn levels() -> Vec<Level> {
+        let first = fixtures::project(fixtures::defs(), Vec::new()).levels[0].clone();
+        let mut second = first.clone();
+        second.identifier = "Level_1".into();
+        second.iid = "level-1".into();
+        second.world_x = 64;
+        second.world_y = -32;
+        second.world_depth = 1;
+        second.px_wid = 128;
+        vec![first, second]
+    }
+
+    #[test]
+    fn levels_read_back_by_name() {
+        let levels = levels();
+        let files = vec![(&levels[0], vec![1, 2, 3]), (&levels[1], vec![4, 5])];
+        let mut file = Vec::new();
+        write(&mut file, "World", &files).unwrap();
+
+        let mut archive = Archive::new(Cursor::new(file)).unwrap();
+        assert_eq!(archive.world(), "World");
+        assert_eq!(archive.entries().len(), 2);
+        let entry = archive.find("level-1").unwrap().clone();
+        assert_eq!(entry.identifier, "Level_1");
+        assert_eq!(
+            (entry.world_x, entry.world_y, entry.world_depth),
+            (64, -32, 1)
+        );
+        assert_eq!((entry.width, entry.height, entry.length), (128, 64, 2));
+        assert_eq!(
+            archive.entries()[0].offset + archive.entries()[0].length,
+            entry.offset
+        );
+
+        assert_eq!(archive.read_level("Level_0").unwrap(), [1, 2, 3]);
+        assert_eq!(archive.read_level("Level_1").unwrap(), [4, 5]);
+        assert!(matches!(
+            archive.read_level("Level_2"),
+            Err(ReadError::MissingLevel(_))
+        ));
+    }
+
+    #[test]
+    fn truncated_levels_are_refused() {
+        let levels = levels();
+        let mut file = Vec::new();
+        write(&mut file, "World", &[(&levels[0], vec![0; 16])]).unwrap();
+        file.truncate(file.len() - 1);
+
+        let mut archive = Archive::new(Cursor::new(file)).unwrap();
+        assert!(matches!(
+            archive.read_level("Level_0"),
+            Err(ReadError::Truncated { .. })
+        ));
+    }
+
+    #[test]
+    fn other_files_are_refused() {
+        assert!(matches!(
+            Archive::new(Cursor::new(b"LVL World 0.".to_vec())),
+            Err(ReadError::BadHeader)
+        ));
+    }
+}
@@ -0,0 +1,500 @@
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+};
+
+use {
+    anyhow::{bail, Context, Result},
+    scopeguard::defer,
+};
+
+use crate::{
+    cache::{self, Key, Manifest, ProjectHashes},
+    compat, convert,
+    error::BatchError,
+    info::{self, InfoTables},
+    runtime::{ExternalLevel, Level, Project},
+};
+
+/// File name pattern used when none is configured.
+pub const DEFAULT_FILENAME: &str = "{identifier}.lvl";
+
+/// Placeholders a file name pattern may contain.
+const PLACEHOLDERS: &[&str] = &["identifier", "iid", "uid", "index"];
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Number of levels converted concurrently
+    pub jobs: usize,
+
+    /// Convert every level, even those whose inputs did not change since the last run
+    pub force: bool,
+
+    /// Output file name of each level, relative to the output directory
+    pub filename: String,
+
+    pub convert: convert::Options,
+}
+
+/// What happened to a single level of the batch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Converted(PathBuf),
+
+    /// The output is already up to date with its inputs
+    Skipped(PathBuf),
+}
+
+/// Everything the workers share, read once up front.
+struct Shared {
+    project: Project,
+    levels: Vec<Level>,
+    tables: InfoTables,
+    options: convert::Options,
+
+    /// Output file name of each level
+    names: Vec<String>,
+
+    hashes: ProjectHashes,
+    info_table_hash: String,
+    options_hash: String,
+    manifest: Manifest,
+    force: bool,
+
+    /// Project file, external level paths are relative to its directory
+    project_path: PathBuf,
+    output_dir: PathBuf,
+}
+
+/// Converts every level of a project into `output_dir`, named after `options.filename`, using
+/// up to `options.jobs` threads. Info tables are merged in order.
+///
+/// Results are returned in the order the levels appear in the project (root levels first,
+/// then the levels of each world), regardless of the order they finished in. Failing levels
+/// do not stop the others, they are collected into a single `BatchError`. Only failing to
+/// read the project or info table aborts the batch.
+///
+/// A cache manifest in the output directory records the inputs of every converted level,
+/// levels whose project definitions, level JSON, info tables and options are unchanged are
+/// skipped unless `options.force` is set.
+pub fn run(
+    project_path: &Path,
+    info_table_paths: &[PathBuf],
+    output_dir: &Path,
+    options: &Options,
+) -> Result<Vec<Outcome>> {
+    let shared = read_inputs(project_path, info_table_paths, output_dir, options)?;
+    let shared = Arc::new(shared);
+    let total = shared.levels.len();
+
+    let next = Arc::new(AtomicUsize::new(0));
+    let (tx, rx) = mpsc::channel();
+    let workers = (0..options.jobs.max(1).min(total.max(1)))
+        .map(|_| {
+            let shared = Arc::clone(&shared);
+            let next = Arc::clone(&next);
+            let tx = tx.clone();
+            thread::spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= shared.levels.len() {
+                    break;
+                }
+                // the receiver outlives the workers, so sending cannot fail
+                let _ = tx.send((index, convert_one(&shared, index)));
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(tx);
+
+    let mut results = (0..total).map(|_| None).collect::<Vec<_>>();
+    for (index, result) in rx {
+        results[index] = Some(result);
+    }
+    for worker in workers {
+        // a panicking worker leaves its level without a result, reported below
+        let _ = worker.join();
+    }
+
+    let mut manifest = shared.manifest.clone();
+    let mut outcomes = Vec::with_capacity(total);
+    let mut failures = Vec::new();
+    for ((level, name), result) in shared.levels.iter().zip(shared.names.iter()).zip(results) {
+        match result {
+            Some(Ok((outcome, key))) => {
+                manifest.levels.insert(name.clone(), key);
+                outcomes.push(outcome);
+            }
+            Some(Err(err)) => {
+                manifest.levels.remove(name);
+                failures.push((level.identifier.clone(), err));
+            }
+            None => {
+                manifest.levels.remove(name);
+                failures.push((
+                    level.identifier.clone(),
+                    anyhow::anyhow!("conversion thread panicked"),
+                ));
+            }
+        }
+    }
+
+    if let Err(err) = manifest.save(output_dir) {
+        failures.push((
+            cache::FILE_NAME.to_owned(),
+            anyhow::Error::new(err).context("failed to write the cache manifest"),
+        ));
+    }
+
+    if failures.is_empty() {
+        Ok(outcomes)
+    } else {
+        Err(BatchError { total, failures }.into())
+    }
+}
+
+fn read_inputs(
+    project_path: &Path,
+    info_table_paths: &[PathBuf],
+    output_dir: &Path,
+    options: &Options,
+) -> Result<Shared> {
+    check_filename(&options.filename)?;
+
+    let raw = fs::read_to_string(project_path)
+        .with_context(|| format!("failed to read {}", project_path.display()))?;
+    let mut project: Project = compat::parse_project(&raw)?;
+    let hashes = ProjectHashes::new(&raw)?;
+
+    let mut tables = InfoTables::default();
+    let mut info_table_hash = cache::Hasher::new();
+    for path in info_table_paths {
+        let raw_tables = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        tables.merge(
+            info::parse(&raw_tables)
+                .with_context(|| format!("failed to parse {}", path.display()))?,
+        );
+        info_table_hash.write(raw_tables.as_bytes());
+    }
+
+    let levels = take_levels(&mut project);
+    let names = levels
+        .iter()
+        .enumerate()
+        .map(|(index, level)| output_name(&options.filename, level, index))
+        .collect::<Vec<_>>();
+    for (index, name) in names.iter().enumerate() {
+        if let Some(other) = names[..index].iter().position(|other| other == name) {
+            bail!(
+                "levels `{}` and `{}` would both be written to {}, use a file name pattern that tells them apart",
+                levels[other].identifier,
+                levels[index].identifier,
+                name
+            );
+        }
+    }
+
+    fs::create_dir_all(output_dir)
+        .with_context(|| format!("failed to create {}", output_dir.display()))?;
+
+    Ok(Shared {
+        levels,
+        project,
+        tables,
+        options: options.convert.clone(),
+        names,
+        hashes,
+        info_table_hash: info_table_hash.finish(),
+        options_hash: cache::hash(serde_json::to_string(&options.convert)?.as_bytes()),
+        manifest: Manifest::load(output_dir),
+        force: options.force,
+        project_path: project_path.to_owned(),
+        output_dir: output_dir.to_owned(),
+    })
+}
+
+fn convert_one(shared: &Shared, index: usize) -> Result<(Outcome, Key)> {
+    let level = &shared.levels[index];
+    let name = &shared.names[index];
+    let path = shared.output_dir.join(name);
+
+    // external levels are hashed along with their stub in the project
+    let mut level_hash = cache::Hasher::new();
+    level_hash.write(shared.hashes.levels[index].as_bytes());
+    let external = ExternalLevel::read(&shared.project_path, level)?;
+    if let Some(external) = &external {
+        level_hash.write(external.raw.as_bytes());
+    }
+
+    let key = Key {
+        project: shared.hashes.project.clone(),
+        level: level_hash.finish(),
+        info_table: shared.info_table_hash.clone(),
+        options: shared.options_hash.clone(),
+    };
+    if !shared.force && shared.manifest.is_fresh(&shared.output_dir, name, &key) {
+        return Ok((Outcome::Skipped(path), key));
+    }
+
+    let parsed;
+    let level = match external {
+        Some(external) => {
+            parsed = external.parse(&shared.project)?;
+            &parsed
+        }
+        None => level,
+    };
+
+    // patterns may place levels in sub directories
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    write_atomic(&path, |mut dst| {
+        convert::level(
+            &shared.project,
+            level,
+            &shared.tables,
+            &shared.options,
+            &mut dst,
+        )?;
+        Ok(())
+    })?;
+    Ok((Outcome::Converted(path), key))
+}
+
+/// Writes `path` through a temporary file renamed over it once complete, so a game
+/// hot-reloading the output never observes a partially written level.
+pub fn write_atomic(path: &Path, write: impl FnOnce(&File) -> Result<()>) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    // after a successful rename there is nothing left to remove
+    defer! {
+        let _ = fs::remove_file(&tmp);
+    }
+
+    let dst = File::create(&tmp).with_context(|| format!("failed to create {}", tmp.display()))?;
+    write(&dst)?;
+    dst.sync_data()?;
+    drop(dst);
+
+    fs::rename(&tmp, path).with_context(|| format!("failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+/// Expands a file name pattern for a level, `index` being its position in project order.
+///
+/// `{identifier}`, `{iid}` and `{uid}` are replaced with the level's own, `{index}` with its
+/// position.
+pub fn output_name(pattern: &str, level: &Level, index: usize) -> String {
+    pattern
+        .replace("{identifier}", &level.identifier)
+        .replace("{iid}", &level.iid)
+        .replace("{uid}", &level.uid.to_string())
+        .replace("{index}", &index.to_string())
+}
+
+/// Checks every `{...}` in a file name pattern is a known placeholder.
+pub fn check_filename(pattern: &str) -> Result<()> {
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => bail!("unclosed `{{` in file name pattern `{}`", pattern),
+        };
+        let placeholder = &rest[start + 1..end];
+        if !PLACEHOLDERS.contains(&placeholder) {
+            bail!(
+                "unknown placeholder `{{{}}}` in file name pattern `{}`, expected one of {{{}}}",
+                placeholder,
+                pattern,
+                PLACEHOLDERS.join("}, {")
+            );
+        }
+        rest = &rest[end + 1..];
+    }
+    Ok(())
+}
+
+/// Moves the levels out of the project, root levels first, then those of each world.
+fn take_levels(project: &mut Project) -> Vec<Level> {
+    let mut levels = std::mem::take(&mut project.levels);
+    for world in project.worlds.iter_mut() {
+        levels.append(&mut world.levels);
+    }
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{filter::LayerFilter, runtime::fixtures};
+
+    /// An empty directory of its own for each test.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "cartographer-batch-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes a project holding `levels` into `dir`, along with an empty info table.
+    fn write_project(dir: &Path, levels: Vec<Value>) -> (PathBuf, Vec<PathBuf>) {
+        let project = dir.join("project.ldtk");
+        let info = dir.join("info.json");
+        let json = fixtures::project_json(fixtures::defs(), levels);
+        fs::write(&project, json.to_string()).unwrap();
+        if !info.is_file() {
+            fs::write(&info, r#"{ "entityTable": [], "tilesetTable": [] }"#).unwrap();
+        }
+        (project, vec![info])
+    }
+
+    fn grid(identifier: &str, uid: i64, cells: &[i64]) -> Value {
+        fixtures::level(identifier, uid, vec![fixtures::int_grid(2, cells)])
+    }
+
+    fn options() -> Options {
+        Options {
+            jobs: 1,
+            force: false,
+            filename: DEFAULT_FILENAME.to_owned(),
+            convert: convert::Options::default(),
+        }
+    }
+
+    /// Whether each level was converted rather than skipped.
+    fn converted(outcomes: Vec<Outcome>) -> Vec<bool> {
+        outcomes
+            .iter()
+            .map(|outcome| matches!(outcome, Outcome::Converted(_)))
+            .collect()
+    }
+
+    #[test]
+    fn levels_are_converted_again_when_their_inputs_change() {
+        let dir = scratch("cache");
+        let output = dir.join("levels");
+        let levels = vec![grid("Level_0", 0, &[1, 0]), grid("Level_1", 1, &[0, 1])];
+        let (project, info) = write_project(&dir, levels);
+        let run = |options: &Options| converted(run(&project, &info, &output, options).unwrap());
+
+        assert_eq!(run(&options()), [true, true]);
+        assert_eq!(run(&options()), [false, false]);
+
+        // level JSON
+        write_project(
+            &dir,
+            vec![grid("Level_0", 0, &[1, 0]), grid("Level_1", 1, &[1, 1])],
+        );
+        assert_eq!(run(&options()), [false, true]);
+
+        // info tables
+        fs::write(
+            &info[0],
+            r#"{ "entityTable": [], "tilesetTable": [], "tagTable": [] }"#,
+        )
+        .unwrap();
+        assert_eq!(run(&options()), [true, true]);
+
+        // options
+        let mut names = options();
+        names.convert.names = true;
+        assert_eq!(run(&names), [true, true]);
+        assert_eq!(run(&names), [false, false]);
+
+        // converter version
+        let manifest = output.join(cache::FILE_NAME);
+        let raw = fs::read_to_string(&manifest).unwrap();
+        let version = format!(r#""version": "{}""#, env!("CARGO_PKG_VERSION"));
+        assert!(raw.contains(&version));
+        fs::write(&manifest, raw.replace(&version, r#""version": "0.0.0""#)).unwrap();
+        assert_eq!(run(&names), [true, true]);
+
+        // deleted outputs, and forced runs
+        fs::remove_file(output.join("Level_1.lvl")).unwrap();
+        assert_eq!(run(&names), [false, true]);
+        names.force = true;
+        assert_eq!(run(&names), [true, true]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn results_keep_project_order_and_collect_failures() {
+        let dir = scratch("order");
+        let output = dir.join("levels");
+        let tiles = fixtures::layer("Tiles", "Tiles", 3, 2, 2);
+        let levels = vec![
+            grid("Level_0", 0, &[1, 0]),
+            grid("Level_1", 1, &[0, 1]),
+            grid("Level_2", 2, &[1, 1]),
+            fixtures::level("Level_3", 3, vec![tiles]),
+        ];
+        let (project, info) = write_project(&dir, levels);
+
+        let mut options = options();
+        options.jobs = 3;
+        options.convert.layers = LayerFilter::parse(&[], &["type:Tiles".to_owned()]).unwrap();
+        let outcomes = run(&project, &info, &output, &options).unwrap();
+        let names = outcomes
+            .iter()
+            .map(|outcome| match outcome {
+                Outcome::Converted(path) => path.file_name().unwrap().to_owned(),
+                Outcome::Skipped(path) => panic!("{} was skipped", path.display()),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["Level_0.lvl", "Level_1.lvl", "Level_2.lvl", "Level_3.lvl"]
+        );
+
+        // the level with a Tiles layer fails, the others are still written
+        options.force = true;
+        options.convert.layers = LayerFilter::default();
+        let err = run(&project, &info, &output, &options).unwrap_err();
+        let err = err.downcast_ref::<BatchError>().unwrap();
+        assert_eq!(err.total, 4);
+        assert_eq!(err.failures.len(), 1);
+        assert_eq!(err.failures[0].0, "Level_3");
+        let manifest = Manifest::load(&output);
+        assert!(manifest.levels.contains_key("Level_2.lvl"));
+        assert!(!manifest.levels.contains_key("Level_3.lvl"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn levels_sharing_an_output_name_are_refused() {
+        let dir = scratch("names");
+        let levels = vec![grid("Level_0", 0, &[1, 0]), grid("Level_1", 1, &[0, 1])];
+        let (project, info) = write_project(&dir, levels);
+
+        let mut options = options();
+        options.filename = "{index}.lvl".to_owned();
+        assert!(run(&project, &info, &dir.join("levels"), &options).is_ok());
+
+        options.filename = "level.lvl".to_owned();
+        let err = run(&project, &info, &dir.join("levels"), &options).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("would both be written to level.lvl"));
+
+        options.filename = "{name}.lvl".to_owned();
+        assert!(run(&project, &info, &dir.join("levels"), &options).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+// records which inputs each LVL file in an output directory was converted from, so a
+// batch can skip levels whose inputs have not changed since the last run
+
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use {
+    serde::{Deserialize, Serialize},
+    serde_json::value::RawValue,
+};
+
+/// Name of the manifest kept next to the converted levels.
+pub const FILE_NAME: &str = ".cartographer-cache.json";
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    /// Version of the converter that wrote the outputs, a different one invalidates them all
+    pub version: String,
+
+    /// Inputs of every output file, keyed by file name
+    pub levels: BTreeMap<String, Key>,
+}
+
+/// Content hashes of everything a single LVL file was converted from.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Key {
+    /// Project definitions and the project settings the converter reads
+    pub project: String,
+
+    /// Level JSON, embedded in the project or from its external file
+    pub level: String,
+
+    /// Info tables, in the order they were merged
+    pub info_table: String,
+
+    /// Options the level was converted with
+    pub options: String,
+}
+
+impl Manifest {
+    /// Reads the manifest of an output directory, a missing, unreadable or outdated one is
+    /// treated as empty so every level gets converted.
+    pub fn load(dir: &Path) -> Self {
+        let manifest = fs::read_to_string(dir.join(FILE_NAME))
+            .ok()
+            .and_then(|raw| serde_json::from_str::<Manifest>(&raw).ok())
+            .unwrap_or_default();
+
+        if manifest.version == env!("CARGO_PKG_VERSION") {
+            manifest
+        } else {
+            Manifest::default()
+        }
+    }
+
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        let manifest = Manifest {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            levels: self.levels.clone(),
+        };
+        fs::write(dir.join(FILE_NAME), serde_json::to_vec_pretty(&manifest)?)
+    }
+
+    /// Whether `name` was converted from exactly these inputs and is still on disk.
+    pub fn is_fresh(&self, dir: &Path, name: &str, key: &Key) -> bool {
+        self.levels.get(name) == Some(key) && dir.join(name).is_file()
+    }
+}
+
+/// Hashes of the parts of a project file that affect conversion.
+#[derive(Debug, Clone)]
+pub struct ProjectHashes {
+    pub project: String,
+
+    /// One per level, root levels first, then those of each world
+    pub levels: Vec<String>,
+}
+
+impl ProjectHashes {
+    pub fn new(raw: &str) -> serde_json::Result<Self> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Raw<'a> {
+            #[serde(borrow)]
+            json_version: &'a RawValue,
+            #[serde(borrow)]
+            default_level_width: Option<&'a RawValue>,
+            #[serde(borrow)]
+            default_level_height: Option<&'a RawValue>,
+            #[serde(borrow)]
+            defs: &'a RawValue,
+            #[serde(borrow)]
+            levels: Vec<&'a RawValue>,
+            #[serde(borrow, default)]
+            worlds: Vec<RawWorld<'a>>,
+        }
+
+        #[derive(Deserialize)]
+        struct RawWorld<'a> {
+            #[serde(borrow)]
+            levels: Vec<&'a RawValue>,
+        }
+
+        let raw: Raw = serde_json::from_str(raw)?;
+
+        let mut project = Hasher::new();
+        project.write(raw.json_version.get().as_bytes());
+        for setting in &[raw.default_level_width, raw.default_level_height] {
+            project.write(setting.map_or("null", |value| value.get()).as_bytes());
+        }
+        project.write(raw.defs.get().as_bytes());
+
+        let levels = raw
+            .levels
+            .iter()
+            .chain(raw.worlds.iter().flat_map(|world| world.levels.iter()))
+            .map(|level| hash(level.get().as_bytes()))
+            .collect();
+
+        Ok(ProjectHashes {
+            project: project.finish(),
+            levels,
+        })
+    }
+}
+
+/// Hex encoded 64-bit FNV-1a hash, stable across platforms and compiler versions unlike the
+/// standard library's `DefaultHasher`.
+pub fn hash(bytes: &[u8]) -> String {
+    let mut hasher = Hasher::new();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// Incremental form of `hash`, for inputs made of several parts.
+pub struct Hasher(u64);
+
+impl Hasher {
+    pub fn new() -> Self {
+        Hasher(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+        // separates consecutive writes, so ("ab", "c") and ("a", "bc") differ
+        self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    pub fn finish(&self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+impl Default for Hasher {
+    fn default() -> Self {
+        Hasher::new()
+    }
+}
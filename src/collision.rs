@@ -0,0 +1,190 @@
+// merges the solid cells of IntGrid layers into axis-aligned rectangles, physics engines want
+// a few dozen boxes rather than one collider per cell
+
+use std::io::{Result, Write};
+
+use {
+    byteorder::{LittleEndian, WriteBytesExt},
+    serde::{Deserialize, Serialize},
+};
+
+/// Which IntGrid values are solid, as set in the `collision` object of the info table.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Collision {
+    /// Solid IntGrid values, nothing is merged when empty
+    #[serde(default)]
+    pub values: Vec<i64>,
+
+    /// IntGrid layers to merge, by identifier, every IntGrid layer when empty
+    #[serde(default)]
+    pub layers: Vec<String>,
+
+    /// Keep writing a grid cell for each solid cell along with the rectangles
+    #[serde(default)]
+    pub keep_cells: bool,
+}
+
+/// A block of cells sharing the same value, in cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub value: i64,
+}
+
+impl Collision {
+    /// Whether the solid cells of this IntGrid layer are merged.
+    pub fn applies_to(&self, layer: &str) -> bool {
+        !self.values.is_empty()
+            && (self.layers.is_empty() || self.layers.iter().any(|name| name == layer))
+    }
+
+    pub fn is_solid(&self, value: i64) -> bool {
+        self.values.contains(&value)
+    }
+
+    /// Takes the settings of another table, unless this one already has solid values.
+    pub fn merge(&mut self, other: Collision) {
+        if self.values.is_empty() {
+            *self = other;
+        }
+    }
+}
+
+/// Covers the solid cells of a grid, left to right and top to bottom like `__intGridCsv`,
+/// with rectangles of a single value each.
+///
+/// The sweep is greedy: from the first cell not covered yet, a rectangle grows right as far as
+/// the value repeats, then down as long as whole rows of it do. This does not always find the
+/// fewest rectangles, but comes close on the shapes levels are made of, in linear time.
+pub fn rectangles(cells: &[i64], width: usize, solid: impl Fn(i64) -> bool) -> Vec<Rect> {
+    let mut rects = Vec::new();
+    if width == 0 {
+        return rects;
+    }
+    let height = (cells.len() + width - 1) / width;
+    let mut covered = vec![false; cells.len()];
+
+    for y in 0..height {
+        for x in 0..width {
+            let index = y * width + x;
+            if index >= cells.len() || covered[index] || !solid(cells[index]) {
+                continue;
+            }
+
+            let value = cells[index];
+            let free = |x: usize, y: usize| {
+                let index = y * width + x;
+                index < cells.len() && !covered[index] && cells[index] == value
+            };
+            let mut w = 1;
+            while x + w < width && free(x + w, y) {
+                w += 1;
+            }
+            let mut h = 1;
+            while y + h < height && (x..x + w).all(|x| free(x, y + h)) {
+                h += 1;
+            }
+
+            for row in y..y + h {
+                for cell in &mut covered[row * width + x..row * width + x + w] {
+                    *cell = true;
+                }
+            }
+            rects.push(Rect {
+                x,
+                y,
+                width: w,
+                height: h,
+                value,
+            });
+        }
+    }
+    rects
+}
+
+pub fn rectangle<W: Write>(f: &mut W, x: i16, y: i16, w: u16, h: u16, value: i8) -> Result<()> {
+    f.write_u8(0x7)?; // collision rectangle instruction
+    f.write_i16::<LittleEndian>(x)?; // x position operand
+    f.write_i16::<LittleEndian>(y)?; // y position operand
+    f.write_u16::<LittleEndian>(w)?; // width operand
+    f.write_u16::<LittleEndian>(h)?; // height operand
+    f.write_i8(value)?; // cell value operand
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: usize, y: usize, width: usize, height: usize, value: i64) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+            value,
+        }
+    }
+
+    #[test]
+    fn rows_merge_into_blocks() {
+        #[rustfmt::skip]
+        let cells = [
+            1, 1, 1, 0,
+            1, 1, 1, 0,
+            0, 0, 1, 1,
+        ];
+        assert_eq!(
+            rectangles(&cells, 4, |value| value != 0),
+            [rect(0, 0, 3, 2, 1), rect(2, 2, 2, 1, 1)]
+        );
+    }
+
+    #[test]
+    fn values_are_never_mixed() {
+        #[rustfmt::skip]
+        let cells = [
+            1, 2, 2,
+            1, 2, 2,
+            3, 3, 1,
+        ];
+        assert_eq!(
+            rectangles(&cells, 3, |value| value != 3),
+            [
+                rect(0, 0, 1, 2, 1),
+                rect(1, 0, 2, 2, 2),
+                rect(2, 2, 1, 1, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn every_solid_cell_is_covered_once() {
+        #[rustfmt::skip]
+        let cells = [
+            1, 0, 1, 1, 1,
+            1, 1, 1, 0, 1,
+            0, 1, 1, 1, 1,
+            1, 1,
+        ];
+        let mut covered = vec![0; cells.len()];
+        for rect in rectangles(&cells, 5, |value| value == 1) {
+            for y in rect.y..rect.y + rect.height {
+                for x in rect.x..rect.x + rect.width {
+                    covered[y * 5 + x] += 1;
+                }
+            }
+        }
+        assert_eq!(covered, cells);
+    }
+
+    #[test]
+    fn empty_grids_have_no_rectangles() {
+        assert!(rectangles(&[], 0, |_| true).is_empty());
+        assert!(rectangles(&[0, 0, 0, 0], 2, |value| value != 0).is_empty());
+    }
+}
@@ -0,0 +1,823 @@
+// normalizes project and level files saved by other LDtk versions into the
+// shape of the structures in `ldtk.rs`, which were generated for `CURRENT`
+
+use std::{fmt, str::FromStr};
+
+use {
+    hashbrown::HashMap,
+    serde::{de::DeserializeOwned, Deserialize},
+    serde_json::{json, Value},
+};
+
+use crate::{error::VersionError, ldtk, runtime};
+
+/// Oldest `jsonVersion` the migrations below can bring up to date.
+pub const OLDEST: Version = Version(0, 7, 0);
+
+/// The `jsonVersion` the structures in `ldtk.rs` were generated from.
+pub const CURRENT: Version = Version(1, 5, 3);
+
+/// Major releases are allowed to break the schema, so anything past the current one is
+/// refused rather than half-converted.
+pub const NEWEST_MAJOR: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(pub u32, pub u32, pub u32);
+
+impl FromStr for Version {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // pre-release suffixes ("1.0.0-beta3") sort with their release
+        let s = s.split(|c| c == '-' || c == '+').next().unwrap_or_default();
+        let mut parts = s.trim().split('.').map(|part| part.parse::<u32>());
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(major)), Some(Ok(minor)), patch, None) => match patch {
+                None => Ok(Version(major, minor, 0)),
+                Some(Ok(patch)) => Ok(Version(major, minor, patch)),
+                Some(Err(_)) => Err(()),
+            },
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+/// Reads the `jsonVersion` of a project and checks it is one we can normalize.
+pub fn detect(raw: &str) -> Result<Version, VersionError> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Header {
+        json_version: Option<String>,
+    }
+
+    let header: Header = serde_json::from_str(raw).map_err(VersionError::Json)?;
+    let found = header.json_version.ok_or(VersionError::Missing)?;
+    check(&found)
+}
+
+/// Checks a `jsonVersion` string against the range of versions we can normalize.
+pub fn check(found: &str) -> Result<Version, VersionError> {
+    let version = found
+        .parse::<Version>()
+        .map_err(|_| VersionError::Unsupported(found.to_owned()))?;
+    if version < OLDEST || version.0 > NEWEST_MAJOR {
+        return Err(VersionError::Unsupported(found.to_owned()));
+    }
+    Ok(version)
+}
+
+/// A project model that can be parsed from files of any supported version, implemented by
+/// both the full `ldtk` model and the trimmed `runtime` view.
+pub trait Project: DeserializeOwned {
+    type Level: DeserializeOwned;
+
+    fn json_version(&self) -> &str;
+
+    fn defaults(&self) -> Defaults;
+}
+
+/// Parses a project file of any supported version into the current structures.
+pub fn parse_project<P: Project>(raw: &str) -> Result<P, VersionError> {
+    let version = detect(raw)?;
+
+    // files already in the current shape skip the detour through `Value`
+    if version >= CURRENT {
+        return serde_json::from_str(raw).map_err(VersionError::Json);
+    }
+
+    let mut value: Value = serde_json::from_str(raw).map_err(VersionError::Json)?;
+    migrate_project(version, &mut value);
+    serde_json::from_value(value).map_err(VersionError::Json)
+}
+
+/// Parses an external level file (`.ldtkl`), which carries no version of its own, using the
+/// version and definitions of the project it belongs to.
+pub fn parse_level<P: Project>(raw: &str, project: &P) -> Result<P::Level, VersionError> {
+    let version = check(project.json_version())?;
+    if version >= CURRENT {
+        return serde_json::from_str(raw).map_err(VersionError::Json);
+    }
+
+    let mut value: Value = serde_json::from_str(raw).map_err(VersionError::Json)?;
+    migrate_level(version, &project.defaults(), &mut value);
+    serde_json::from_value(value).map_err(VersionError::Json)
+}
+
+// both models name the fields the defaults come from the same, so a single body implements
+// the trait for each of them
+macro_rules! impl_project {
+    ($project:ty, $level:ty) => {
+        impl Project for $project {
+            type Level = $level;
+
+            fn json_version(&self) -> &str {
+                &self.json_version
+            }
+
+            fn defaults(&self) -> Defaults {
+                let entities = self
+                    .defs
+                    .entities
+                    .iter()
+                    .map(|def| {
+                        let defaults = EntityDefaults {
+                            width: def.width,
+                            height: def.height,
+                            pivot: [def.pivot_x, def.pivot_y],
+                            color: def.color.clone(),
+                            tags: def.tags.clone(),
+                        };
+                        (def.uid, defaults)
+                    })
+                    .collect();
+
+                Defaults {
+                    bg_color: self.default_level_bg_color.clone(),
+                    entities,
+                }
+            }
+        }
+    };
+}
+
+impl_project!(ldtk::LdtkJson, ldtk::Level);
+impl_project!(runtime::Project, runtime::Level);
+
+/// Brings a whole project, including its embedded levels, up to the current schema.
+pub fn migrate_project(version: Version, project: &mut Value) {
+    for step in PROJECT_STEPS.iter().filter(|step| version < step.since) {
+        (step.apply)(project);
+    }
+
+    let defaults = Defaults::from_value(project);
+    for level in levels_mut(project) {
+        migrate_level(version, &defaults, level);
+    }
+}
+
+/// Brings a single level up to the current schema.
+pub fn migrate_level(version: Version, defaults: &Defaults, level: &mut Value) {
+    for step in LEVEL_STEPS.iter().filter(|step| version < step.since) {
+        (step.apply)(defaults, level);
+    }
+}
+
+/// Values taken from the project that older level data left implicit.
+#[derive(Debug, Default)]
+pub struct Defaults {
+    bg_color: String,
+    entities: HashMap<i64, EntityDefaults>,
+}
+
+#[derive(Debug)]
+struct EntityDefaults {
+    width: i64,
+    height: i64,
+    pivot: [f64; 2],
+    color: String,
+    tags: Vec<String>,
+}
+
+impl Defaults {
+    fn from_value(project: &Value) -> Self {
+        let entities = project["defs"]["entities"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|def| {
+                let defaults = EntityDefaults {
+                    width: def["width"].as_i64().unwrap_or(0),
+                    height: def["height"].as_i64().unwrap_or(0),
+                    pivot: [
+                        def["pivotX"].as_f64().unwrap_or(0.0),
+                        def["pivotY"].as_f64().unwrap_or(0.0),
+                    ],
+                    color: def["color"].as_str().unwrap_or("#000000").to_owned(),
+                    tags: serde_json::from_value(def["tags"].clone()).unwrap_or_default(),
+                };
+                Some((def["uid"].as_i64()?, defaults))
+            })
+            .collect();
+
+        Defaults {
+            bg_color: project["defaultLevelBgColor"]
+                .as_str()
+                .unwrap_or("#000000")
+                .to_owned(),
+            entities,
+        }
+    }
+}
+
+struct ProjectStep {
+    /// First version whose files already contain this change
+    since: Version,
+    apply: fn(&mut Value),
+}
+
+struct LevelStep {
+    /// First version whose files already contain this change
+    since: Version,
+    apply: fn(&Defaults, &mut Value),
+}
+
+const PROJECT_STEPS: &[ProjectStep] = &[
+    ProjectStep {
+        since: Version(0, 8, 0),
+        apply: |project| {
+            default(&mut project["defs"], "levelFields", json!([]));
+            default(&mut project["defs"], "externalEnums", json!([]));
+            default(project, "defaultLevelBgColor", json!("#40465B"));
+        },
+    },
+    ProjectStep {
+        since: Version(0, 9, 3),
+        apply: |project| {
+            // `exportPng` was replaced by `imageExportMode`
+            let mode = match project["exportPng"].as_bool() {
+                Some(true) => "OneImagePerLayer",
+                _ => "None",
+            };
+            default(project, "imageExportMode", json!(mode));
+        },
+    },
+    ProjectStep {
+        since: Version(1, 0, 0),
+        apply: |project| {
+            let iid = iid(0, 0, 0, 0);
+            default(project, "iid", json!(iid));
+            default(project, "worlds", json!([]));
+            default(project, "flags", json!([]));
+            default(project, "identifierStyle", json!("Capitalize"));
+            default(project, "levelNamePattern", json!("Level_%idx"));
+            default(project, "exportTiled", json!(false));
+            default(project, "backupOnSave", json!(false));
+            default(project, "minifyJson", json!(false));
+            default(project, "externalLevels", json!(false));
+            default(project, "defaultPivotX", json!(0.0));
+            default(project, "defaultPivotY", json!(0.0));
+            default(project, "defaultGridSize", json!(16));
+            default(project, "defaultEntityWidth", json!(16));
+            default(project, "defaultEntityHeight", json!(16));
+            default(project, "bgColor", json!("#40465B"));
+            default(project, "nextUid", json!(0));
+            default(project, "appBuildId", json!(0.0));
+        },
+    },
+    ProjectStep {
+        since: Version(1, 1, 0),
+        apply: |project| {
+            default(project, "customCommands", json!([]));
+            default(project, "simplifiedExport", json!(false));
+        },
+    },
+    ProjectStep {
+        since: Version(1, 2, 0),
+        apply: |project| {
+            default(project, "toc", json!([]));
+        },
+    },
+    ProjectStep {
+        since: Version(1, 3, 0),
+        apply: |project| {
+            default(project, "backupLimit", json!(10));
+            default(project, "exportLevelBg", json!(true));
+            let iid = iid(0, 0, 0, 1);
+            default(project, "dummyWorldIid", json!(iid));
+        },
+    },
+    ProjectStep {
+        since: Version(1, 4, 0),
+        apply: |project| {
+            // the table of content switched from bare references to `instancesData`
+            for entry in array_mut(&mut project["toc"]) {
+                let instances = entry["instances"].as_array().cloned().unwrap_or_default();
+                let data = instances
+                    .into_iter()
+                    .map(|iids| {
+                        json!({
+                            "iids": iids,
+                            "fields": null,
+                            "widPx": 0,
+                            "heiPx": 0,
+                            "worldX": 0,
+                            "worldY": 0,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                default(entry, "instancesData", Value::Array(data));
+            }
+        },
+    },
+    // definitions are all filled in one pass, the defaults never overwrite what a file has
+    ProjectStep {
+        since: CURRENT,
+        apply: migrate_defs,
+    },
+];
+
+const LEVEL_STEPS: &[LevelStep] = &[
+    LevelStep {
+        since: Version(0, 8, 0),
+        apply: |defaults, level| {
+            let color = level["bgColor"]
+                .as_str()
+                .unwrap_or(&defaults.bg_color)
+                .to_owned();
+            default(level, "__bgColor", json!(color));
+            default(level, "bgPivotX", json!(0.5));
+            default(level, "bgPivotY", json!(0.5));
+            default(level, "fieldInstances", json!([]));
+        },
+    },
+    LevelStep {
+        since: Version(1, 0, 0),
+        apply: |_, level| {
+            let uid = level["uid"].as_i64().unwrap_or(0);
+            default(level, "iid", json!(iid(uid, 0, 0, 0)));
+            let color = level["__bgColor"].clone();
+            default(level, "__smartColor", color);
+            default(level, "__neighbours", json!([]));
+            default(level, "useAutoIdentifier", json!(false));
+            default(level, "worldDepth", json!(0));
+
+            // neighbours used to point at level uids, which we derived the iids from above
+            for neighbour in array_mut(&mut level["__neighbours"]) {
+                let uid = neighbour["levelUid"].as_i64().unwrap_or(0);
+                default(neighbour, "levelIid", json!(iid(uid, 0, 0, 0)));
+            }
+        },
+    },
+    // instance data is normalized in one pass, see `migrate_layer`
+    LevelStep {
+        since: CURRENT,
+        apply: |defaults, level| {
+            // some releases stored the computed background position under `bgPos`, which
+            // now holds the positioning mode, and keep the computed one in `__bgPos`
+            if level["bgPos"].is_object() {
+                let pos = level["bgPos"].take();
+                level["__bgPos"] = pos;
+            }
+            default(level, "__bgPos", Value::Null);
+
+            let uid = level["uid"].as_i64().unwrap_or(0);
+            for (index, layer) in array_mut(&mut level["layerInstances"]).enumerate() {
+                migrate_layer(defaults, uid, index as i64, layer);
+            }
+        },
+    },
+];
+
+fn migrate_layer(defaults: &Defaults, level: i64, index: i64, layer: &mut Value) {
+    let def = layer["layerDefUid"].as_i64().unwrap_or(0);
+    default(layer, "iid", json!(iid(level, def, index, 0)));
+    default(layer, "levelId", json!(level));
+    default(layer, "__opacity", json!(1.0));
+    default(layer, "pxOffsetX", json!(0));
+    default(layer, "pxOffsetY", json!(0));
+    let (x, y) = (layer["pxOffsetX"].clone(), layer["pxOffsetY"].clone());
+    default(layer, "__pxTotalOffsetX", x);
+    default(layer, "__pxTotalOffsetY", y);
+    default(layer, "autoLayerTiles", json!([]));
+    default(layer, "gridTiles", json!([]));
+    default(layer, "entityInstances", json!([]));
+    default(layer, "optionalRules", json!([]));
+    default(layer, "seed", json!(0));
+    default(layer, "visible", json!(true));
+
+    // 0.7.0 files can still hold IntGrid layers only as a sparse list of `{ coordId, v }`
+    // where `v` is the 0-based index of the value, the dense CSV form starts values at 1 and
+    // keeps 0 as empty
+    if layer.get("intGridCsv").is_none() {
+        let (w, h) = (
+            layer["__cWid"].as_i64().unwrap_or(0),
+            layer["__cHei"].as_i64().unwrap_or(0),
+        );
+        let mut csv = vec![0i64; (w.max(0) * h.max(0)) as usize];
+        for cell in layer["intGrid"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+        {
+            let coord = cell["coordId"].as_i64().unwrap_or(-1);
+            if coord < 0 {
+                continue;
+            }
+            if let Some(slot) = csv.get_mut(coord as usize) {
+                *slot = cell["v"].as_i64().unwrap_or(0) + 1;
+            }
+        }
+        layer["intGridCsv"] = json!(csv);
+    }
+
+    let grid_size = layer["__gridSize"].as_i64().unwrap_or(1).max(1);
+    for (n, entity) in array_mut(&mut layer["entityInstances"]).enumerate() {
+        let def = defaults
+            .entities
+            .get(&entity["defUid"].as_i64().unwrap_or(0));
+        default(entity, "iid", json!(iid(level, index, n as i64, 1)));
+        default(entity, "fieldInstances", json!([]));
+        if let Some(def) = def {
+            default(entity, "width", json!(def.width));
+            default(entity, "height", json!(def.height));
+            default(entity, "__pivot", json!(def.pivot));
+            default(entity, "__smartColor", json!(def.color));
+            default(entity, "__tags", json!(def.tags));
+        }
+        default(entity, "__pivot", json!([0.0, 0.0]));
+        default(entity, "__smartColor", json!("#000000"));
+        default(entity, "__tags", json!([]));
+        default(entity, "width", json!(0));
+        default(entity, "height", json!(0));
+
+        let px = entity["px"].as_array().cloned().unwrap_or_default();
+        let grid = px
+            .iter()
+            .map(|v| v.as_i64().unwrap_or(0) / grid_size)
+            .collect::<Vec<_>>();
+        default(entity, "__grid", json!(grid));
+
+        for field in array_mut(&mut entity["fieldInstances"]) {
+            default(field, "realEditorValues", json!([]));
+        }
+    }
+
+    for tiles in &["gridTiles", "autoLayerTiles"] {
+        for tile in array_mut(&mut layer[*tiles]) {
+            default(tile, "a", json!(1.0));
+            default(tile, "f", json!(0));
+            default(tile, "d", json!([]));
+        }
+    }
+}
+
+fn migrate_defs(project: &mut Value) {
+    let defs = &mut project["defs"];
+
+    for layer in array_mut(&mut defs["layers"]) {
+        default(layer, "autoRuleGroups", json!([]));
+        default(layer, "canSelectWhenInactive", json!(true));
+        default(layer, "displayOpacity", json!(1.0));
+        default(layer, "inactiveOpacity", json!(1.0));
+        default(layer, "excludedTags", json!([]));
+        default(layer, "requiredTags", json!([]));
+        default(layer, "uiFilterTags", json!([]));
+        default(layer, "guideGridWid", json!(0));
+        default(layer, "guideGridHei", json!(0));
+        default(layer, "hideFieldsWhenInactive", json!(true));
+        default(layer, "hideInList", json!(false));
+        default(layer, "intGridValues", json!([]));
+        default(layer, "intGridValuesGroups", json!([]));
+        default(layer, "parallaxFactorX", json!(0.0));
+        default(layer, "parallaxFactorY", json!(0.0));
+        default(layer, "parallaxScaling", json!(true));
+        default(layer, "pxOffsetX", json!(0));
+        default(layer, "pxOffsetY", json!(0));
+        default(layer, "renderInWorldView", json!(true));
+        default(layer, "tilePivotX", json!(0.0));
+        default(layer, "tilePivotY", json!(0.0));
+
+        for value in array_mut(&mut layer["intGridValues"]) {
+            default(value, "groupUid", json!(0));
+        }
+
+        for group in array_mut(&mut layer["autoRuleGroups"]) {
+            default(group, "biomeRequirementMode", json!(0));
+            default(group, "isOptional", json!(false));
+            default(group, "requiredBiomeValues", json!([]));
+            default(group, "usesWizard", json!(false));
+            default(group, "rules", json!([]));
+
+            for rule in array_mut(&mut group["rules"]) {
+                // single tile ids became tile rectangles in 1.5.0
+                let rects = rule["tileIds"]
+                    .as_array()
+                    .map(|ids| ids.iter().map(|id| json!([id])).collect::<Vec<_>>())
+                    .unwrap_or_default();
+                default(rule, "tileRectsIds", Value::Array(rects));
+                default(rule, "alpha", json!(1.0));
+                default(rule, "checker", json!("None"));
+                default(rule, "tileMode", json!("Single"));
+                default(rule, "flipX", json!(false));
+                default(rule, "flipY", json!(false));
+                default(rule, "breakOnMatch", json!(true));
+                default(rule, "chance", json!(1.0));
+                default(rule, "perlinActive", json!(false));
+                default(rule, "perlinOctaves", json!(2.0));
+                default(rule, "perlinScale", json!(0.2));
+                default(rule, "perlinSeed", json!(0.0));
+                default(rule, "pivotX", json!(0.0));
+                default(rule, "pivotY", json!(0.0));
+                default(rule, "tileRandomXMin", json!(0));
+                default(rule, "tileRandomXMax", json!(0));
+                default(rule, "tileRandomYMin", json!(0));
+                default(rule, "tileRandomYMax", json!(0));
+                default(rule, "tileXOffset", json!(0));
+                default(rule, "tileYOffset", json!(0));
+                default(rule, "xModulo", json!(1));
+                default(rule, "yModulo", json!(1));
+                default(rule, "xOffset", json!(0));
+                default(rule, "yOffset", json!(0));
+            }
+        }
+    }
+
+    for entity in array_mut(&mut defs["entities"]) {
+        default(entity, "allowOutOfBounds", json!(false));
+        default(entity, "exportToToc", json!(false));
+        default(entity, "fieldDefs", json!([]));
+        default(entity, "fillOpacity", json!(1.0));
+        default(entity, "lineOpacity", json!(1.0));
+        default(entity, "tileOpacity", json!(1.0));
+        default(entity, "hollow", json!(false));
+        default(entity, "keepAspectRatio", json!(false));
+        default(entity, "limitBehavior", json!("MoveLastOne"));
+        default(entity, "limitScope", json!("PerLevel"));
+        default(entity, "maxCount", json!(0));
+        default(entity, "nineSliceBorders", json!([]));
+        default(entity, "renderMode", json!("Rectangle"));
+        default(entity, "resizableX", json!(false));
+        default(entity, "resizableY", json!(false));
+        default(entity, "showName", json!(true));
+        default(entity, "tags", json!([]));
+        default(entity, "tileRenderMode", json!("FitInside"));
+    }
+
+    for entity in array_mut(&mut defs["entities"]) {
+        array_mut(&mut entity["fieldDefs"]).for_each(migrate_field_def);
+    }
+    array_mut(&mut defs["levelFields"]).for_each(migrate_field_def);
+
+    for tileset in array_mut(&mut defs["tilesets"]) {
+        let grid = tileset["tileGridSize"].as_i64().unwrap_or(1).max(1);
+        let (w, h) = (
+            tileset["pxWid"].as_i64().unwrap_or(0),
+            tileset["pxHei"].as_i64().unwrap_or(0),
+        );
+        default(tileset, "__cWid", json!(w / grid));
+        default(tileset, "__cHei", json!(h / grid));
+        default(tileset, "customData", json!([]));
+        default(tileset, "enumTags", json!([]));
+        default(tileset, "savedSelections", json!([]));
+        default(tileset, "tags", json!([]));
+        default(tileset, "padding", json!(0));
+        default(tileset, "spacing", json!(0));
+    }
+
+    for kind in &["enums", "externalEnums"] {
+        for def in array_mut(&mut defs[*kind]) {
+            default(def, "tags", json!([]));
+            for value in array_mut(&mut def["values"]) {
+                default(value, "color", json!(0));
+            }
+        }
+    }
+}
+
+fn migrate_field_def(field: &mut Value) {
+    default(field, "allowedRefs", json!("OnlySame"));
+    default(field, "allowedRefTags", json!([]));
+    default(field, "allowOutOfLevelRef", json!(true));
+    default(field, "autoChainRef", json!(true));
+    default(field, "canBeNull", json!(false));
+    default(field, "editorAlwaysShow", json!(false));
+    default(field, "editorCutLongValues", json!(true));
+    default(field, "editorDisplayMode", json!("Hidden"));
+    default(field, "editorDisplayPos", json!("Above"));
+    default(field, "editorDisplayScale", json!(1.0));
+    default(field, "editorLinkStyle", json!("StraightArrow"));
+    default(field, "editorShowInWorld", json!(true));
+    default(field, "exportToToc", json!(false));
+    default(field, "isArray", json!(false));
+    default(field, "searchable", json!(false));
+    default(field, "symmetricalRef", json!(false));
+    default(field, "useForSmartColor", json!(false));
+}
+
+/// Every level of a project, whether stored at the root or inside `worlds`.
+fn levels_mut(project: &mut Value) -> Vec<&mut Value> {
+    let mut all = Vec::new();
+    let root = match project.as_object_mut() {
+        Some(root) => root,
+        None => return all,
+    };
+
+    for (key, value) in root.iter_mut() {
+        match key.as_str() {
+            "levels" => all.extend(array_mut(value)),
+            "worlds" => {
+                for world in array_mut(value) {
+                    if let Some(levels) = world.get_mut("levels") {
+                        all.extend(array_mut(levels));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    all
+}
+
+fn array_mut(value: &mut Value) -> impl Iterator<Item = &mut Value> {
+    value
+        .as_array_mut()
+        .into_iter()
+        .flat_map(|array| array.iter_mut())
+}
+
+/// Inserts `value` under `key` unless the object already has it.
+fn default(object: &mut Value, key: &str, value: Value) {
+    if let Some(map) = object.as_object_mut() {
+        map.entry(key).or_insert(value);
+    }
+}
+
+/// Builds a stable stand-in for the instance identifiers LDtk started writing in 1.0.0, so
+/// converting the same old file twice yields the same iids.
+fn iid(a: i64, b: i64, c: i64, kind: i64) -> String {
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-000000000000",
+        a as u32, b as u16, c as u16, kind as u16
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 0.7.0 project, before levels had iids, background colors or depths, and before
+    /// entities carried their tags and size.
+    fn project() -> Value {
+        json!({
+            "jsonVersion": "0.7.0",
+            "defs": {
+                "entities": [{
+                    "color": "#FF00FF",
+                    "height": 32,
+                    "identifier": "Door",
+                    "pivotX": 0.5,
+                    "pivotY": 1.0,
+                    "tags": ["solid"],
+                    "uid": 7,
+                    "width": 16
+                }],
+                "layers": [
+                    {
+                        "__type": "Entities",
+                        "identifier": "Entities",
+                        "uid": 2
+                    },
+                    {
+                        "__type": "IntGrid",
+                        "identifier": "Walls",
+                        "uid": 3
+                    }
+                ],
+                "tilesets": []
+            },
+            "levels": [
+                {
+                    "identifier": "Level_0",
+                    "layerInstances": [level()["layerInstances"][0].clone()],
+                    "__neighbours": [{ "dir": "e", "levelUid": 1 }],
+                    "pxWid": 64,
+                    "pxHei": 64,
+                    "uid": 0,
+                    "worldX": 0,
+                    "worldY": 0
+                },
+                {
+                    "bgColor": "#102030",
+                    "identifier": "Level_1",
+                    "layerInstances": [{
+                        "__cWid": 2,
+                        "__cHei": 2,
+                        "__gridSize": 16,
+                        "__identifier": "Walls",
+                        "__type": "IntGrid",
+                        "intGrid": [{ "coordId": 1, "v": 0 }, { "coordId": 2, "v": 2 }],
+                        "layerDefUid": 3
+                    }],
+                    "pxWid": 64,
+                    "pxHei": 64,
+                    "uid": 1,
+                    "worldX": 64,
+                    "worldY": 0
+                }
+            ]
+        })
+    }
+
+    fn level() -> Value {
+        json!({
+            "identifier": "Level_2",
+            "layerInstances": [{
+                "__cWid": 4,
+                "__cHei": 4,
+                "__gridSize": 16,
+                "__identifier": "Entities",
+                "__type": "Entities",
+                "entityInstances": [{
+                    "__identifier": "Door",
+                    "defUid": 7,
+                    "px": [40, 24]
+                }],
+                "gridTiles": [],
+                "layerDefUid": 2
+            }],
+            "pxWid": 64,
+            "pxHei": 64,
+            "uid": 2,
+            "worldX": 128,
+            "worldY": 0
+        })
+    }
+
+    #[test]
+    fn versions_parse_with_their_suffixes() {
+        assert_eq!("1.5.3".parse(), Ok(Version(1, 5, 3)));
+        assert_eq!("0.9".parse(), Ok(Version(0, 9, 0)));
+        assert_eq!("1.0.0-beta3".parse(), Ok(Version(1, 0, 0)));
+        assert_eq!("1.a.0".parse::<Version>(), Err(()));
+        assert_eq!("1.2.3.4".parse::<Version>(), Err(()));
+    }
+
+    #[test]
+    fn versions_outside_the_migrations_are_refused() {
+        assert_eq!(check("0.7.0").unwrap(), OLDEST);
+        assert_eq!(check("1.99.0").unwrap(), Version(1, 99, 0));
+        assert!(check("0.6.2").is_err());
+        assert!(check("2.0.0").is_err());
+        assert!(detect("{}").is_err());
+    }
+
+    #[test]
+    fn old_projects_are_filled_with_defaults() {
+        let raw = project().to_string();
+        let project = parse_project::<runtime::Project>(&raw).unwrap();
+        assert_eq!(project.default_level_bg_color, "#40465B");
+        assert_eq!(project.defs.entities[0].tile_render_mode, "FitInside");
+        assert!(project.defs.layers[0].int_grid_values.is_empty());
+
+        let (first, second) = (&project.levels[0], &project.levels[1]);
+        assert_eq!(first.bg_color, "#40465B");
+        assert_eq!(second.bg_color, "#102030");
+        assert_eq!(first.world_depth, 0);
+        assert_ne!(first.iid, second.iid);
+        assert_eq!(first.neighbours[0].level_iid, second.iid);
+
+        let layer = &first.layer_instances.as_ref().unwrap()[0];
+        let entity = &layer.entity_instances[0];
+        assert_eq!(entity.tags, ["solid"]);
+        assert_eq!((entity.width, entity.height), (16, 32));
+        assert_eq!(entity.pivot, [0.5, 1.0]);
+    }
+
+    #[test]
+    fn external_levels_use_the_version_of_their_project() {
+        let project = parse_project::<runtime::Project>(&project().to_string()).unwrap();
+        let level = parse_level(&level().to_string(), &project).unwrap();
+        assert_eq!(level.bg_color, "#40465B");
+        assert!(level.neighbours.is_empty());
+        let entity = &level.layer_instances.unwrap()[0].entity_instances[0];
+        assert_eq!(entity.tags, ["solid"]);
+        assert_eq!(entity.width, 16);
+    }
+
+    #[test]
+    fn sparse_int_grids_become_csv() {
+        let project = parse_project::<runtime::Project>(&project().to_string()).unwrap();
+        let layer = &project.levels[1].layer_instances.as_ref().unwrap()[0];
+        assert_eq!(layer.int_grid_csv, [0, 1, 3, 0]);
+
+        let entities = &project.levels[0].layer_instances.as_ref().unwrap()[0];
+        assert_eq!(entities.int_grid_csv, [0; 16]);
+    }
+
+    #[test]
+    fn current_files_are_read_as_they_are() {
+        let mut current = project();
+        migrate_project(OLDEST, &mut current);
+        current["jsonVersion"] = json!("1.5.3");
+        current["levels"][0]["iid"] = json!("kept");
+        let project = parse_project::<runtime::Project>(&current.to_string()).unwrap();
+        assert_eq!(project.levels[0].iid, "kept");
+        let layer = &project.levels[1].layer_instances.as_ref().unwrap()[0];
+        assert_eq!(layer.int_grid_csv, [0, 1, 3, 0]);
+
+        // nothing fills in what a current file leaves out
+        let mut level = level();
+        assert!(parse_level(&level.to_string(), &project).is_err());
+        migrate_level(OLDEST, &project.defaults(), &mut level);
+        level["layerInstances"][0]["iid"] = json!("kept");
+        let level = parse_level(&level.to_string(), &project).unwrap();
+        assert_eq!(level.layer_instances.unwrap()[0].iid, "kept");
+    }
+}
@@ -0,0 +1,101 @@
+// project level settings read from a `cartographer.toml`, so every developer and CI export
+// with the same inputs and options without repeating them on the command line
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use {
+    anyhow::{Context, Result},
+    serde::Deserialize,
+};
+
+/// Name of the configuration file looked up in the working directory.
+pub const FILE_NAME: &str = "cartographer.toml";
+
+/// Every setting is optional, command line arguments take precedence over the file.
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// LDtk project file
+    pub project: Option<PathBuf>,
+
+    /// Directory the levels are written to
+    pub output: Option<PathBuf>,
+
+    /// Output file name of each level, see `batch::output_name` for the placeholders
+    pub filename: Option<String>,
+
+    /// Info tables, merged in order
+    #[serde(default)]
+    pub info_tables: Vec<PathBuf>,
+
+    /// Layers to convert, every layer when empty, see `filter::LayerRule` for the syntax
+    #[serde(default)]
+    pub layers: Vec<String>,
+
+    /// Layers to leave out, even when `layers` includes them
+    #[serde(default)]
+    pub exclude_layers: Vec<String>,
+
+    /// Tags of the entities to convert, every entity when empty
+    #[serde(default)]
+    pub entity_tags: Vec<String>,
+
+    /// Tags of the entities to leave out
+    #[serde(default)]
+    pub exclude_entity_tags: Vec<String>,
+
+    /// LVL format version to write
+    pub format: Option<u8>,
+
+    /// End every level with a checksum of its content
+    #[serde(default)]
+    pub checksum: bool,
+
+    /// Compress every level, format 1 only
+    #[serde(default)]
+    pub compress: bool,
+
+    /// Write level and layer names
+    #[serde(default)]
+    pub names: bool,
+
+    /// Position entities by their top left corner rather than their pivot
+    #[serde(default)]
+    pub top_left: bool,
+}
+
+impl Config {
+    /// Reads a configuration file, relative paths in it are resolved against the directory
+    /// the file is in rather than the working directory.
+    pub fn read(path: &Path) -> Result<Config> {
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let mut config: Config =
+            toml::from_str(&raw).with_context(|| format!("failed to parse {}", path.display()))?;
+
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        for path in config
+            .project
+            .iter_mut()
+            .chain(config.output.iter_mut())
+            .chain(config.info_tables.iter_mut())
+        {
+            *path = dir.join(&*path);
+        }
+
+        Ok(config)
+    }
+
+    /// Reads `cartographer.toml` from `dir`, `None` when there is no such file.
+    pub fn find(dir: &Path) -> Result<Option<Config>> {
+        let path = dir.join(FILE_NAME);
+        if path.is_file() {
+            Config::read(&path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
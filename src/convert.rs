@@ -0,0 +1,1075 @@
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::{
+    collision, decal, entity,
+    error::{ConvertError, ErrorKind},
+    filter::{EntityFilter, LayerFilter},
+    global, grid,
+    info::InfoTables,
+    layer, navigation, outline,
+    runtime::{EntityInstance, Level, Project, TilesetRectangle},
+    section::{self, Section},
+    string::{self, StringTable},
+    tile,
+};
+
+/// Settings that change what gets written, a cached output is only reused if they match.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Options {
+    /// Layers to convert
+    pub layers: LayerFilter,
+
+    /// Entities to convert, on top of the info table's own filter
+    pub entities: EntityFilter,
+
+    /// LVL format version to write, one of `global::FORMATS`
+    pub format: u8,
+
+    /// End the file with a checksum of its content
+    pub checksum: bool,
+
+    /// Compress everything after the header, Format 1 only
+    pub compress: bool,
+
+    /// Write the level and layer names, through a string table
+    pub names: bool,
+
+    /// Position entities by their top left corner rather than their pivot
+    pub top_left: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            layers: LayerFilter::default(),
+            entities: EntityFilter::default(),
+            format: global::DEFAULT_FORMAT,
+            checksum: false,
+            compress: false,
+            names: false,
+            top_left: false,
+        }
+    }
+}
+
+/// Writes a single level, whose layer instances must already be loaded, as an LVL file.
+pub fn level<W: Write>(
+    project: &Project,
+    map: &Level,
+    infotable: &InfoTables,
+    options: &Options,
+    out: &mut W,
+) -> Result<(), ConvertError> {
+    use std::convert::{TryFrom, TryInto};
+
+    let level = map.identifier.as_str();
+    let io = |err| ConvertError::new(level, ErrorKind::Io(err));
+    let out_of_range =
+        |field, value| ConvertError::new(level, ErrorKind::OutOfRange { field, value });
+
+    let ent_table = &infotable.entity_table;
+    let ts_table = &infotable.tileset_table;
+
+    if !global::FORMATS.contains(&options.format) {
+        return Err(ConvertError::new(
+            level,
+            ErrorKind::UnsupportedFormat(options.format),
+        ));
+    }
+
+    // every section is assembled in memory, as the Format 1 directory needs their lengths
+    let mut sections = Vec::new();
+
+    // strings are collected along the way, and written up front in the metadata section
+    let mut strings = StringTable::new();
+    let level_name = strings.insert(level).map_err(io)?;
+
+    // iterate through layers
+    let layers = map
+        .layer_instances
+        .as_ref()
+        .ok_or_else(|| ConvertError::new(level, ErrorKind::MissingLayers))?;
+    for layer in layers
+        .iter()
+        .filter(|layer| options.layers.includes(project, layer))
+    {
+        let name = layer.identifier.as_str();
+        let io = |err| ConvertError::in_layer(level, name, ErrorKind::Io(err));
+        let out_of_range = |field, value| {
+            ConvertError::in_layer(level, name, ErrorKind::OutOfRange { field, value })
+        };
+
+        let mut data = Vec::new();
+        let dst = &mut data;
+
+        // signal layer type
+        let typ = match layer.layer_instance_type.as_ref() {
+            "IntGrid" => 1,
+            "Entities" => 3,
+            typ => {
+                return Err(ConvertError::in_layer(
+                    level,
+                    name,
+                    ErrorKind::UnsupportedLayerType(typ.to_owned()),
+                ));
+            }
+        };
+        layer::set_type(dst, typ).map_err(io)?;
+
+        // set width and height
+        let grid_size = layer
+            .grid_size
+            .try_into()
+            .map_err(|_| out_of_range("__gridSize", layer.grid_size))?;
+        layer::set_width(dst, grid_size).map_err(io)?;
+        layer::set_height(dst, grid_size).map_err(io)?;
+        if options.names {
+            layer::set_name(dst, strings.insert(name).map_err(io)?).map_err(io)?;
+        }
+
+        if layer.layer_instance_type == "IntGrid" {
+            // solid cells are replaced by rectangles, unless the info table keeps them
+            let collision = &infotable.collision;
+            let merge = collision.applies_to(name);
+            let skip = |cell| merge && !collision.keep_cells && collision.is_solid(cell);
+
+            // grid cells, their coordinates follow from their index in the CSV
+            let grid = &layer.int_grid_csv;
+            let columns = layer.c_wid.max(1) as usize;
+            for (index, cell) in grid.iter().enumerate() {
+                if skip(*cell) {
+                    continue;
+                }
+                let (x, y) = ((index % columns) as i64, (index / columns) as i64);
+                grid::cell_set(
+                    dst,
+                    x.try_into().map_err(|_| out_of_range("intGridCsv", x))?,
+                    y.try_into().map_err(|_| out_of_range("intGridCsv", y))?,
+                    i8::try_from(*cell).map_err(|_| out_of_range("intGridCsv", *cell))?,
+                )
+                .map_err(io)?;
+            }
+
+            // collision rectangles
+            if merge {
+                let rects = collision::rectangles(grid, layer.c_wid.max(0) as usize, |cell| {
+                    collision.is_solid(cell)
+                });
+                let position = |value: usize| {
+                    i16::try_from(value).map_err(|_| out_of_range("intGridCsv", value as i64))
+                };
+                let size = |value: usize| {
+                    u16::try_from(value).map_err(|_| out_of_range("intGridCsv", value as i64))
+                };
+                for rect in rects {
+                    collision::rectangle(
+                        dst,
+                        position(rect.x)?,
+                        position(rect.y)?,
+                        size(rect.width)?,
+                        size(rect.height)?,
+                        i8::try_from(rect.value)
+                            .map_err(|_| out_of_range("intGridCsv", rect.value))?,
+                    )
+                    .map_err(io)?;
+                }
+            }
+
+            // outlines, one value at a time
+            let outlines = &infotable.outline;
+            if outlines.applies_to(name) {
+                let width = layer.c_wid.max(0) as usize;
+                for value in outlines.values.iter() {
+                    let operand =
+                        i8::try_from(*value).map_err(|_| out_of_range("outline", *value))?;
+                    let corner = |value: i32| {
+                        i16::try_from(value)
+                            .map_err(|_| out_of_range("intGridCsv", i64::from(value)))
+                    };
+                    for chain in outline::chains(grid, width, *value) {
+                        let points = outline::simplify(&chain, outlines.tolerance)
+                            .iter()
+                            .map(|(x, y)| Ok((corner(*x)?, corner(*y)?)))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        if u16::try_from(points.len()).is_err() {
+                            return Err(out_of_range("outline", points.len() as i64));
+                        }
+                        outline::polygon(dst, operand, &points).map_err(io)?;
+                    }
+                }
+            }
+        }
+
+        // entities, decals being set aside for a layer of their own
+        let mut decals = Vec::new();
+        let entities = &layer.entity_instances;
+        let entities = entities.iter().filter(|entity| {
+            infotable.entity_filter.includes(&entity.tags)
+                && options.entities.includes(&entity.tags)
+        });
+        for entity in entities {
+            let missing = |field: &str| {
+                ConvertError::in_layer(
+                    level,
+                    name,
+                    ErrorKind::MissingField {
+                        identifier: entity.identifier.clone(),
+                        iid: entity.iid.clone(),
+                        field: field.to_owned(),
+                    },
+                )
+            };
+            let invalid = |field: &str, expected| {
+                ConvertError::in_layer(
+                    level,
+                    name,
+                    ErrorKind::InvalidField {
+                        identifier: entity.identifier.clone(),
+                        iid: entity.iid.clone(),
+                        field: field.to_owned(),
+                        expected,
+                    },
+                )
+            };
+
+            let settings = &infotable.decals;
+            if settings.is_decal(&entity.tags) {
+                let malformed = |field, expected, found| {
+                    ConvertError::in_layer(
+                        level,
+                        name,
+                        ErrorKind::MalformedEntity {
+                            identifier: entity.identifier.clone(),
+                            iid: entity.iid.clone(),
+                            field,
+                            expected,
+                            found,
+                        },
+                    )
+                };
+                // a decal is nothing but its tile, without one it is refused, while one from a
+                // tileset the tileset table leaves out is skipped like the tiles of that tileset
+                if entity.tile.is_none() {
+                    return Err(missing("__tile"));
+                }
+                let (tileset, rect) = match sprite(project, infotable, entity) {
+                    Some((tileset, rect)) => (
+                        i32::try_from(tileset)
+                            .map_err(|_| out_of_range("tilesetTable", tileset))?,
+                        rect,
+                    ),
+                    None => continue,
+                };
+                let (x, y) = match entity.px.as_slice() {
+                    [x, y] => (*x as f64, *y as f64),
+                    px => return Err(malformed("px", 2, px.len())),
+                };
+                let (pivot_x, pivot_y) = match entity.pivot.as_slice() {
+                    [pivot_x, pivot_y] => (*pivot_x, *pivot_y),
+                    pivot => return Err(malformed("__pivot", 2, pivot.len())),
+                };
+                let (w, h) = (entity.width as f64, entity.height as f64);
+
+                // decals are placed by their center, and stretched over the entity bounds
+                let center_x = (x + (0.5 - pivot_x) * w).round() as i64;
+                let center_y = (y + (0.5 - pivot_y) * h).round() as i64;
+                let scale = |size: f64, tile_size: i64| match tile_size {
+                    0 => 1.0,
+                    tile_size => (size / tile_size as f64) as f32,
+                };
+                let rot = match field(entity, &settings.rotation_field).map_err(missing)? {
+                    None => 0,
+                    Some((field, value)) => {
+                        degrees(value).ok_or_else(|| invalid(field, "a number"))?
+                    }
+                };
+                let tint = match field(entity, &settings.tint_field).map_err(missing)? {
+                    None => decal::WHITE,
+                    Some((field, value)) => value
+                        .as_str()
+                        .and_then(decal::tint)
+                        .ok_or_else(|| invalid(field, "a color"))?,
+                };
+                let coordinate =
+                    |value: i64| value.try_into().map_err(|_| out_of_range("__tile", value));
+                let center =
+                    |value: i64| i32::try_from(value).map_err(|_| out_of_range("px", value));
+                decal::decal(
+                    &mut decals,
+                    tileset,
+                    (
+                        coordinate(rect.x)?,
+                        coordinate(rect.y)?,
+                        coordinate(rect.w)?,
+                        coordinate(rect.h)?,
+                    ),
+                    (center(center_x)?, center(center_y)?),
+                    (scale(w, rect.w), scale(h, rect.h)),
+                    rot,
+                    tint,
+                )
+                .map_err(io)?;
+                continue;
+            }
+
+            for entry in ent_table.iter() {
+                if entity.identifier.eq(entry.name.as_str()) {
+                    // NOTE: [x,y] are effected by optional layer offsets
+                    // which just aren't taken into account here at all
+                    let (x, y) = match entity.px.as_slice() {
+                        [x, y] => (x, y),
+                        px => {
+                            return Err(ConvertError::in_layer(
+                                level,
+                                name,
+                                ErrorKind::MalformedEntity {
+                                    identifier: entity.identifier.clone(),
+                                    iid: entity.iid.clone(),
+                                    field: "px",
+                                    expected: 2,
+                                    found: px.len(),
+                                },
+                            ))
+                        }
+                    };
+                    let (pivot_x, pivot_y) = match entity.pivot.as_slice() {
+                        [pivot_x, pivot_y] => (pivot_x, pivot_y),
+                        pivot => {
+                            return Err(ConvertError::in_layer(
+                                level,
+                                name,
+                                ErrorKind::MalformedEntity {
+                                    identifier: entity.identifier.clone(),
+                                    iid: entity.iid.clone(),
+                                    field: "__pivot",
+                                    expected: 2,
+                                    found: pivot.len(),
+                                },
+                            ))
+                        }
+                    };
+                    let w = entity.width;
+                    let h = entity.height;
+
+                    // the origin is where the position lies within the entity, its pivot
+                    // unless positions are moved to the top left corner
+                    let mut origin_x = (pivot_x * w as f64).round() as i64;
+                    let mut origin_y = (pivot_y * h as f64).round() as i64;
+                    let (mut x, mut y) = (*x, *y);
+                    if options.top_left {
+                        x -= origin_x;
+                        y -= origin_y;
+                        origin_x = 0;
+                        origin_y = 0;
+                    }
+
+                    // rotation and flips come from the fields the entity table names
+                    let rot = match field(entity, &entry.rotation_field).map_err(missing)? {
+                        None => 0,
+                        Some((field, value)) => {
+                            degrees(value).ok_or_else(|| invalid(field, "a number"))?
+                        }
+                    };
+                    let flip = |setting| match field(entity, setting).map_err(missing)? {
+                        None => Ok(false),
+                        Some((field, value)) => {
+                            value.as_bool().ok_or_else(|| invalid(field, "a boolean"))
+                        }
+                    };
+                    let flipped_x = flip(&entry.flip_x_field)?;
+                    let flipped_y = flip(&entry.flip_y_field)?;
+                    let position = |field, value: i64| {
+                        i32::try_from(value).map_err(|_| out_of_range(field, value))
+                    };
+                    let size = |field, value: i64| {
+                        u32::try_from(value).map_err(|_| out_of_range(field, value))
+                    };
+                    entity::create(
+                        dst,
+                        position("entityTable", entry.value)?,
+                        position("px", x)?,
+                        position("px", y)?,
+                        size("width", w)?,
+                        size("height", h)?,
+                        rot,
+                        flipped_x,
+                        flipped_y,
+                        position("__pivot", origin_x)?,
+                        position("__pivot", origin_y)?,
+                    )
+                    .map_err(io)?;
+
+                    // tags apply to the entity created just before
+                    if !infotable.tag_table.is_empty() {
+                        let tags = entity
+                            .tags
+                            .iter()
+                            .filter_map(|tag| {
+                                infotable.tag_table.iter().find(|entry| &entry.name == tag)
+                            })
+                            .map(|entry| {
+                                i32::try_from(entry.value)
+                                    .map_err(|_| out_of_range("tagTable", entry.value))
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+                        if u16::try_from(tags.len()).is_err() {
+                            return Err(out_of_range("__tags", tags.len() as i64));
+                        }
+                        if !tags.is_empty() {
+                            entity::set_tags(dst, &tags).map_err(io)?;
+                        }
+                    }
+
+                    // so does its sprite, when its tileset is in the tileset table
+                    if let Some((tileset, rect)) = sprite(project, infotable, entity) {
+                        let def = project
+                            .defs
+                            .entities
+                            .iter()
+                            .find(|def| def.uid == entity.def_uid);
+                        let mode = def.map_or("FitInside", |def| def.tile_render_mode.as_str());
+                        let mode = entity::render_mode(mode).ok_or_else(|| {
+                            ConvertError::in_layer(
+                                level,
+                                name,
+                                ErrorKind::UnsupportedRenderMode(mode.to_owned()),
+                            )
+                        })?;
+                        let mut borders = [0; 4];
+                        let nine_slice = def
+                            .map(|def| &def.nine_slice_borders)
+                            .filter(|borders| mode == entity::NINE_SLICE && borders.len() == 4);
+                        for (border, value) in
+                            borders.iter_mut().zip(nine_slice.into_iter().flatten())
+                        {
+                            *border = (*value)
+                                .try_into()
+                                .map_err(|_| out_of_range("nineSliceBorders", *value))?;
+                        }
+                        let coordinate = |value: i64| {
+                            value.try_into().map_err(|_| out_of_range("__tile", value))
+                        };
+                        entity::set_sprite(
+                            dst,
+                            i32::try_from(tileset)
+                                .map_err(|_| out_of_range("tilesetTable", tileset))?,
+                            (
+                                coordinate(rect.x)?,
+                                coordinate(rect.y)?,
+                                coordinate(rect.w)?,
+                                coordinate(rect.h)?,
+                            ),
+                            mode,
+                            borders,
+                        )
+                        .map_err(io)?;
+                    }
+                }
+            }
+        }
+
+        // tiles
+        for tile in layer.grid_tiles.iter() {
+            for entry in ts_table.iter() {
+                // TODO: use tileset_def_uid & override_tileset_uid instead
+                let tileset_name = layer.tileset_rel_path.clone().unwrap_or_default();
+                if tileset_name.eq(entry.name.as_str()) {
+                    let malformed = |field, found| {
+                        ConvertError::in_layer(
+                            level,
+                            name,
+                            ErrorKind::MalformedTile {
+                                tile: tile.t,
+                                field,
+                                expected: 2,
+                                found,
+                            },
+                        )
+                    };
+
+                    // NOTE: same as above, optional layer offsets
+                    // aren't taken into account here
+                    let (x, y) = match tile.px.as_slice() {
+                        [x, y] => (x, y),
+                        px => return Err(malformed("px", px.len())),
+                    };
+                    let (src_x, src_y) = match tile.src.as_slice() {
+                        [src_x, src_y] => (src_x, src_y),
+                        src => return Err(malformed("src", src.len())),
+                    };
+                    tile::new(
+                        dst,
+                        entry.value as i32,
+                        *x as u32,
+                        *y as u32,
+                        *src_x as u16,
+                        *src_y as u16,
+                    )
+                    .map_err(io)?;
+                }
+            }
+        }
+
+        sections.push(Section::layer(typ, data));
+
+        // decals follow the entity layer they were placed in
+        if !decals.is_empty() {
+            let mut data = Vec::new();
+            let dst = &mut data;
+            layer::set_type(dst, decal::LAYER_TYPE).map_err(io)?;
+            layer::set_width(dst, grid_size).map_err(io)?;
+            layer::set_height(dst, grid_size).map_err(io)?;
+            if options.names {
+                layer::set_name(dst, strings.insert(name).map_err(io)?).map_err(io)?;
+            }
+            dst.extend_from_slice(&decals);
+            sections.push(Section::layer(decal::LAYER_TYPE, data));
+        }
+
+        // the navigation graph follows the layer it was computed from
+        let nav = &infotable.navigation;
+        if layer.layer_instance_type == "IntGrid" && nav.applies_to(name) {
+            let mut data = Vec::new();
+            let dst = &mut data;
+            let (platforms, links) = nav.graph(&layer.int_grid_csv, layer.c_wid.max(0) as usize);
+            let position = |value: usize| {
+                i16::try_from(value).map_err(|_| out_of_range("intGridCsv", value as i64))
+            };
+            let index = |value: usize| {
+                u16::try_from(value).map_err(|_| out_of_range("navigation", value as i64))
+            };
+            for platform in platforms {
+                navigation::platform(
+                    dst,
+                    position(platform.x)?,
+                    position(platform.y)?,
+                    u16::try_from(platform.length)
+                        .map_err(|_| out_of_range("intGridCsv", platform.length as i64))?,
+                    index(platform.component)?,
+                )
+                .map_err(io)?;
+            }
+            for link in links {
+                navigation::link(dst, index(link.from)?, index(link.to)?, link.kind).map_err(io)?;
+            }
+            sections.push(Section::navigation(data));
+        }
+    }
+
+    // global level properties
+    let mut metadata = Vec::new();
+    let dst = &mut metadata;
+    if options.names {
+        string::table(dst, &strings).map_err(io)?;
+        global::set_name(dst, level_name).map_err(io)?;
+    }
+    let width = project.default_level_width.unwrap_or(0);
+    let height = project.default_level_height.unwrap_or(0);
+    global::set_width(
+        dst,
+        width
+            .try_into()
+            .map_err(|_| out_of_range("defaultLevelWidth", width))?,
+    )
+    .map_err(io)?;
+    global::set_height(
+        dst,
+        height
+            .try_into()
+            .map_err(|_| out_of_range("defaultLevelHeight", height))?,
+    )
+    .map_err(io)?;
+    sections.insert(0, Section::metadata(metadata));
+
+    let mut flags = 0;
+    if options.checksum {
+        flags |= section::FLAG_CHECKSUM;
+    }
+    if options.compress {
+        flags |= section::FLAG_DEFLATE;
+    }
+    section::write(out, options.format, flags, &sections).map_err(io)
+}
+
+/// Value of the entity field an entity table setting names, `None` when the setting is unset
+/// or the field is null. Fails with the field identifier when the entity has no such field.
+fn field<'a>(
+    entity: &'a EntityInstance,
+    setting: &'a Option<String>,
+) -> Result<Option<(&'a str, &'a serde_json::Value)>, &'a str> {
+    let identifier = match setting {
+        Some(identifier) => identifier.as_str(),
+        None => return Ok(None),
+    };
+    let field = entity
+        .field_instances
+        .iter()
+        .find(|field| field.identifier == identifier)
+        .ok_or(identifier)?;
+    Ok(field
+        .value
+        .as_ref()
+        .filter(|value| !value.is_null())
+        .map(|value| (identifier, value)))
+}
+
+/// Tileset ref and source rectangle of the tile an entity is displayed with. `None` when the
+/// entity has no tile, or the tileset table leaves its tileset out.
+fn sprite<'a>(
+    project: &Project,
+    infotable: &InfoTables,
+    entity: &'a EntityInstance,
+) -> Option<(i64, &'a TilesetRectangle)> {
+    let rect = entity.tile.as_ref()?;
+    let def = project
+        .defs
+        .tilesets
+        .iter()
+        .find(|def| def.uid == rect.tileset_uid)?;
+    let rel_path = def.rel_path.as_ref()?;
+    let entry = infotable
+        .tileset_table
+        .iter()
+        .find(|entry| &entry.name == rel_path)?;
+    Some((entry.value, rect))
+}
+
+/// Rotation in degrees, from 0 to 359, held by an Int or Float field. `None` when the field
+/// does not hold a number.
+fn degrees(value: &serde_json::Value) -> Option<i16> {
+    // the remainder always fits
+    value
+        .as_f64()
+        .map(|degrees| (degrees.round() as i64).rem_euclid(360) as i16)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        info::{EntityTableEntry, TagTableEntry, TilesetTableEntry},
+        reader::{self, Command},
+        runtime::fixtures,
+    };
+
+    /// Converts the only level of a project with the default options.
+    fn convert(project: &Project, tables: &InfoTables) -> Vec<Command> {
+        let mut out = Vec::new();
+        level(
+            project,
+            &project.levels[0],
+            tables,
+            &Options::default(),
+            &mut out,
+        )
+        .unwrap();
+        reader::read_all(Cursor::new(out)).unwrap()
+    }
+
+    /// Converts the only level of a project with the default options, expecting it to fail.
+    fn convert_err(project: &Project, tables: &InfoTables) -> ErrorKind {
+        let mut out = Vec::new();
+        level(
+            project,
+            &project.levels[0],
+            tables,
+            &Options::default(),
+            &mut out,
+        )
+        .unwrap_err()
+        .kind
+    }
+
+    #[test]
+    fn grid_cells_advance_along_rows() {
+        let project = fixtures::project(
+            fixtures::defs(),
+            vec![fixtures::int_grid(3, &[1, 0, 2, 0, 1, 0, 2, 2, 1])],
+        );
+        let cells = convert(&project, &InfoTables::default())
+            .into_iter()
+            .filter_map(|command| match command {
+                Command::GridCell { x, y, value } if value != 0 => Some((x, y, value)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            cells,
+            [
+                (0, 0, 1),
+                (2, 0, 2),
+                (1, 1, 1),
+                (0, 2, 2),
+                (1, 2, 2),
+                (2, 2, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn values_past_the_operand_range_are_refused() {
+        let project = fixtures::project(fixtures::defs(), vec![fixtures::int_grid(2, &[1, 200])]);
+        let tables = InfoTables::default();
+        assert!(matches!(
+            convert_err(&project, &tables),
+            ErrorKind::OutOfRange {
+                field: "intGridCsv",
+                value: 200
+            }
+        ));
+
+        // cells merged into collision rectangles are checked as well
+        let mut tables = InfoTables::default();
+        tables.collision.values = vec![200];
+        assert!(matches!(
+            convert_err(&project, &tables),
+            ErrorKind::OutOfRange {
+                field: "intGridCsv",
+                value: 200
+            }
+        ));
+
+        // so are the values outlined, straight from the info table
+        let project = fixtures::project(fixtures::defs(), vec![fixtures::int_grid(2, &[1, 0])]);
+        let mut tables = InfoTables::default();
+        tables.outline.values = vec![1, 200];
+        assert!(matches!(
+            convert_err(&project, &tables),
+            ErrorKind::OutOfRange {
+                field: "outline",
+                value: 200
+            }
+        ));
+    }
+
+    #[test]
+    fn outlines_past_the_operand_range_are_refused() {
+        // the last cell fits a grid cell, but its right edge is one column too far
+        let mut cells = vec![0; 1 << 15];
+        cells[(1 << 15) - 1] = 1;
+        let project =
+            fixtures::project(fixtures::defs(), vec![fixtures::int_grid(1 << 15, &cells)]);
+        let mut tables = InfoTables::default();
+        tables.outline.values = vec![1];
+
+        let err = level(
+            &project,
+            &project.levels[0],
+            &tables,
+            &Options::default(),
+            &mut Vec::new(),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ErrorKind::OutOfRange {
+                field: "intGridCsv",
+                value: 32768
+            }
+        ));
+    }
+
+    #[test]
+    fn entities_are_placed_by_their_pivot_or_top_left_corner() {
+        let mut tables = InfoTables::default();
+        tables.entity_table.push(EntityTableEntry {
+            name: "Door".into(),
+            value: 1,
+            width: 16,
+            height: 16,
+            rotation_field: None,
+            flip_x_field: None,
+            flip_y_field: None,
+        });
+        let mut door = fixtures::entity("Door", (24, 32), &[], json!([]));
+        door["__pivot"] = json!([0.5, 1.0]);
+        let project = fixtures::project(fixtures::defs(), vec![fixtures::entities(vec![door])]);
+
+        let placement = |options: &Options| {
+            let mut out = Vec::new();
+            level(&project, &project.levels[0], &tables, options, &mut out).unwrap();
+            reader::read_all(Cursor::new(out))
+                .unwrap()
+                .into_iter()
+                .filter_map(|command| match command {
+                    Command::Entity {
+                        x,
+                        y,
+                        origin_x,
+                        origin_y,
+                        ..
+                    } => Some((x, y, origin_x, origin_y)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(placement(&Options::default()), [(24, 32, 8, 16)]);
+        let top_left = Options {
+            top_left: true,
+            ..Options::default()
+        };
+        assert_eq!(placement(&top_left), [(16, 16, 0, 0)]);
+
+        let door = fixtures::entity("Door", (1 << 31, 0), &[], json!([]));
+        let project = fixtures::project(fixtures::defs(), vec![fixtures::entities(vec![door])]);
+        assert!(matches!(
+            convert_err(&project, &tables),
+            ErrorKind::OutOfRange { field: "px", value } if value == 1 << 31
+        ));
+    }
+
+    #[test]
+    fn sprites_follow_the_tile_of_their_entity() {
+        let mut tables = InfoTables::default();
+        tables.entity_table.push(EntityTableEntry {
+            name: "Sign".into(),
+            value: 1,
+            width: 16,
+            height: 16,
+            rotation_field: None,
+            flip_x_field: None,
+            flip_y_field: None,
+        });
+        tables.tileset_table.push(TilesetTableEntry {
+            name: "tiles.png".into(),
+            value: 7,
+        });
+        let with = |mode: &str, tileset_uid| {
+            let mut defs = fixtures::defs();
+            defs["entities"] = json!([{
+                "color": "#FFFFFF",
+                "height": 16,
+                "identifier": "Sign",
+                "nineSliceBorders": [1, 2, 3, 4],
+                "pivotX": 0.0,
+                "pivotY": 0.0,
+                "tags": [],
+                "tileRenderMode": mode,
+                "uid": 3,
+                "width": 16
+            }]);
+            defs["tilesets"] = json!([
+                { "identifier": "Tiles", "relPath": "tiles.png", "uid": 5 },
+                { "identifier": "Props", "relPath": "props.png", "uid": 6 }
+            ]);
+            let mut sign = fixtures::entity("Sign", (0, 0), &[], json!([]));
+            sign["__tile"] = json!({ "tilesetUid": tileset_uid, "x": 16, "y": 8, "w": 16, "h": 8 });
+            fixtures::project(defs, vec![fixtures::entities(vec![sign])])
+        };
+        let sprites = |project| {
+            convert(&project, &tables)
+                .into_iter()
+                .filter(|command| matches!(command, Command::EntitySprite { .. }))
+                .collect::<Vec<_>>()
+        };
+
+        let sprite = |mode, borders| Command::EntitySprite {
+            tileset: 7,
+            x: 16,
+            y: 8,
+            width: 16,
+            height: 8,
+            mode,
+            borders,
+        };
+        assert_eq!(sprites(with("Repeat", 5)), [sprite(entity::REPEAT, [0; 4])]);
+        assert_eq!(
+            sprites(with("NineSlice", 5)),
+            [sprite(entity::NINE_SLICE, [1, 2, 3, 4])]
+        );
+
+        // tiles of tilesets the tileset table leaves out are skipped
+        assert_eq!(sprites(with("Repeat", 6)), []);
+
+        assert!(matches!(
+            convert_err(&with("Spiral", 5), &tables),
+            ErrorKind::UnsupportedRenderMode(mode) if mode == "Spiral"
+        ));
+    }
+
+    #[test]
+    fn tags_in_the_tag_table_follow_their_entity() {
+        let mut tables = InfoTables::default();
+        for name in &["Slime", "Coin"] {
+            tables.entity_table.push(EntityTableEntry {
+                name: name.to_string(),
+                value: 1,
+                width: 16,
+                height: 16,
+                rotation_field: None,
+                flip_x_field: None,
+                flip_y_field: None,
+            });
+        }
+        let project = fixtures::project(
+            fixtures::defs(),
+            vec![fixtures::entities(vec![
+                fixtures::entity("Slime", (0, 0), &["enemy", "editorOnly"], json!([])),
+                fixtures::entity("Coin", (16, 0), &["pickup"], json!([])),
+                fixtures::entity("Slime", (32, 0), &["enemy", "boss"], json!([])),
+            ])],
+        );
+        let tags = |tables: &InfoTables| {
+            convert(&project, tables)
+                .into_iter()
+                .filter_map(|command| match command {
+                    Command::Entity { x, .. } => Some((x, Vec::new())),
+                    Command::EntityTags(tags) => Some((-1, tags)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // without a tag table no tags are written
+        assert_eq!(tags(&tables), [(0, vec![]), (16, vec![]), (32, vec![])]);
+
+        tables.tag_table = vec![
+            TagTableEntry {
+                name: "enemy".into(),
+                value: 1,
+            },
+            TagTableEntry {
+                name: "boss".into(),
+                value: 2,
+            },
+        ];
+        tables.entity_filter.exclude_tags = vec!["editorOnly".into()];
+        assert_eq!(
+            tags(&tables),
+            [(16, vec![]), (32, vec![]), (-1, vec![1, 2])]
+        );
+    }
+
+    #[test]
+    fn rotation_and_flips_come_from_entity_fields() {
+        let mut tables = InfoTables::default();
+        tables.entity_table.push(EntityTableEntry {
+            name: "Door".into(),
+            value: 1,
+            width: 16,
+            height: 16,
+            rotation_field: Some("rot".into()),
+            flip_x_field: Some("flip".into()),
+            flip_y_field: None,
+        });
+        let fields = |rot, flip| {
+            json!([
+                { "__identifier": "rot", "__value": rot },
+                { "__identifier": "flip", "__value": flip }
+            ])
+        };
+        let with =
+            |entity| fixtures::project(fixtures::defs(), vec![fixtures::entities(vec![entity])]);
+
+        let transforms = |project| {
+            convert(&project, &tables)
+                .into_iter()
+                .filter_map(|command| match command {
+                    Command::Entity {
+                        rotation,
+                        flipped_x,
+                        flipped_y,
+                        ..
+                    } => Some((rotation, flipped_x, flipped_y)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let door = fixtures::entity("Door", (0, 0), &[], fields(json!(-90.0), json!(true)));
+        assert_eq!(transforms(with(door)), [(270, true, false)]);
+        let door = fixtures::entity("Door", (0, 0), &[], fields(json!(null), json!(null)));
+        assert_eq!(transforms(with(door)), [(0, false, false)]);
+
+        let door = fixtures::entity("Door", (0, 0), &[], fields(json!(90), json!(1)));
+        assert!(matches!(
+            convert_err(&with(door), &tables),
+            ErrorKind::InvalidField { field, expected: "a boolean", .. } if field == "flip"
+        ));
+        let door = fixtures::entity("Door", (0, 0), &[], json!([]));
+        assert!(matches!(
+            convert_err(&with(door), &tables),
+            ErrorKind::MissingField { field, .. } if field == "rot"
+        ));
+    }
+
+    #[test]
+    fn decals_are_refused_without_their_fields_or_tile() {
+        let mut tables = InfoTables::default();
+        tables.decals.tags = vec!["decal".into()];
+        tables.decals.rotation_field = Some("rot".into());
+        tables.decals.tint_field = Some("tint".into());
+        tables.tileset_table.push(TilesetTableEntry {
+            name: "tiles.png".into(),
+            value: 7,
+        });
+        let mut defs = fixtures::defs();
+        defs["tilesets"] = json!([{ "identifier": "Tiles", "relPath": "tiles.png", "uid": 5 }]);
+        let with = |fields| {
+            let mut vine = fixtures::entity("Vine", (16, 16), &["decal"], fields);
+            vine["__tile"] = json!({ "tilesetUid": 5, "x": 8, "y": 0, "w": 8, "h": 16 });
+            fixtures::project(defs.clone(), vec![fixtures::entities(vec![vine])])
+        };
+
+        let fields = json!([
+            { "__identifier": "rot", "__value": 90 },
+            { "__identifier": "tint", "__value": "#FF8000" }
+        ]);
+        let decals = convert(&with(fields), &tables)
+            .into_iter()
+            .filter(|command| matches!(command, Command::Decal { .. }))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            decals,
+            [Command::Decal {
+                tileset: 7,
+                tile_x: 8,
+                tile_y: 0,
+                tile_width: 8,
+                tile_height: 16,
+                x: 24,
+                y: 24,
+                scale_x: 2.0,
+                scale_y: 1.0,
+                rotation: 90,
+                tint: 0xFF80_00FF,
+            }]
+        );
+
+        let fields = json!([{ "__identifier": "rot", "__value": 90 }]);
+        assert!(matches!(
+            convert_err(&with(fields), &tables),
+            ErrorKind::MissingField { field, .. } if field == "tint"
+        ));
+        let fields = json!([
+            { "__identifier": "rot", "__value": "90" },
+            { "__identifier": "tint", "__value": null }
+        ]);
+        assert!(matches!(
+            convert_err(&with(fields), &tables),
+            ErrorKind::InvalidField { field, expected: "a number", .. } if field == "rot"
+        ));
+
+        tables.decals.rotation_field = None;
+        tables.decals.tint_field = None;
+        let vine = fixtures::entity("Vine", (16, 16), &["decal"], json!([]));
+        let project = fixtures::project(defs.clone(), vec![fixtures::entities(vec![vine])]);
+        assert!(matches!(
+            convert_err(&project, &tables),
+            ErrorKind::MissingField { field, .. } if field == "__tile"
+        ));
+
+        let mut vine = fixtures::entity("Vine", (1 << 31, 16), &["decal"], json!([]));
+        vine["__tile"] = json!({ "tilesetUid": 5, "x": 8, "y": 0, "w": 8, "h": 16 });
+        let project = fixtures::project(defs, vec![fixtures::entities(vec![vine])]);
+        assert!(matches!(
+            convert_err(&project, &tables),
+            ErrorKind::OutOfRange { field: "px", .. }
+        ));
+    }
+}
@@ -0,0 +1,81 @@
+// free-placed sprites, scaled, rotated and tinted, which LDtk has no layer for: entities tagged
+// as decals are written to a decal layer of their own, right after the entity layer holding
+// them, instead of as entities
+
+use std::io::{Result, Write};
+
+use {
+    byteorder::{LittleEndian, WriteBytesExt},
+    serde::{Deserialize, Serialize},
+};
+
+/// Layer type of decal layers.
+pub const LAYER_TYPE: u8 = 2;
+
+/// Tint of decals without a tint field, opaque white.
+pub const WHITE: u32 = 0xFFFF_FFFF;
+
+/// Which entities are decals, as set in the `decals` object of the info table.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Decals {
+    /// Entity tags marking decals, nothing is exported when empty
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Int or Float field holding the rotation in degrees, none when unset
+    #[serde(default)]
+    pub rotation_field: Option<String>,
+
+    /// Color field tinting the decal, white when unset
+    #[serde(default)]
+    pub tint_field: Option<String>,
+}
+
+impl Decals {
+    /// Whether an entity with these tags is a decal.
+    pub fn is_decal(&self, tags: &[String]) -> bool {
+        self.tags.iter().any(|tag| tags.contains(tag))
+    }
+
+    /// Takes the settings of another table, unless this one already has decal tags.
+    pub fn merge(&mut self, other: Decals) {
+        if self.tags.is_empty() {
+            *self = other;
+        }
+    }
+}
+
+/// Parses an LDtk `#RRGGBB` color into an opaque `0xRRGGBBAA` tint.
+pub fn tint(color: &str) -> Option<u32> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let rgb = u32::from_str_radix(hex, 16).ok()?;
+    Some(rgb << 8 | 0xFF)
+}
+
+pub fn decal<W: Write>(
+    f: &mut W,
+    tileset: i32,
+    (tile_x, tile_y, tile_w, tile_h): (u16, u16, u16, u16),
+    (x, y): (i32, i32),
+    (scale_x, scale_y): (f32, f32),
+    rot: i16,
+    tint: u32,
+) -> Result<()> {
+    f.write_u8(0xC)?; // decal instruction
+    f.write_i32::<LittleEndian>(tileset)?; // tileset operand
+    f.write_u16::<LittleEndian>(tile_x)?; // source x operand
+    f.write_u16::<LittleEndian>(tile_y)?; // source y operand
+    f.write_u16::<LittleEndian>(tile_w)?; // source width operand
+    f.write_u16::<LittleEndian>(tile_h)?; // source height operand
+    f.write_i32::<LittleEndian>(x)?; // x operand
+    f.write_i32::<LittleEndian>(y)?; // y operand
+    f.write_f32::<LittleEndian>(scale_x)?; // x scale operand
+    f.write_f32::<LittleEndian>(scale_y)?; // y scale operand
+    f.write_i16::<LittleEndian>(rot)?; // rotation operand
+    f.write_u32::<LittleEndian>(tint)?; // tint operand
+    Ok(())
+}
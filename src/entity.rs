@@ -1,10 +1,49 @@
-use std::fs::File;
-use std::io::Result;
+use std::{
+    convert::TryFrom,
+    io::{Error, ErrorKind, Result, Write},
+};
 
 use byteorder::{LittleEndian, WriteBytesExt};
 
-pub fn create(
-    mut f: &File,
+/// How an entity sprite fills the entity bounds, as the LDtk `tileRenderMode` of the same name.
+pub const COVER: u8 = 0;
+pub const FIT_INSIDE: u8 = 1;
+pub const REPEAT: u8 = 2;
+pub const STRETCH: u8 = 3;
+pub const FULL_SIZE_CROPPED: u8 = 4;
+pub const FULL_SIZE_UNCROPPED: u8 = 5;
+pub const NINE_SLICE: u8 = 6;
+
+const RENDER_MODES: [&str; 7] = [
+    "Cover",
+    "FitInside",
+    "Repeat",
+    "Stretch",
+    "FullSizeCropped",
+    "FullSizeUncropped",
+    "NineSlice",
+];
+
+/// Render mode of an LDtk `tileRenderMode`, `None` for modes the LVL format does not know.
+pub fn render_mode(name: &str) -> Option<u8> {
+    RENDER_MODES
+        .iter()
+        .position(|mode| *mode == name)
+        .map(|mode| mode as u8)
+}
+
+/// LDtk name of a render mode.
+pub fn render_mode_name(mode: u8) -> &'static str {
+    RENDER_MODES
+        .get(mode as usize)
+        .copied()
+        .unwrap_or("unknown")
+}
+
+// one parameter per operand of the instruction
+#[allow(clippy::too_many_arguments)]
+pub fn create<W: Write>(
+    f: &mut W,
     ent: i32,
     x: i32,
     y: i32,
@@ -13,6 +52,8 @@ pub fn create(
     rot: i16,
     fx: bool,
     fy: bool,
+    ox: i32,
+    oy: i32,
 ) -> Result<()> {
     f.write_u8(0x3)?; // entity create instruction
     f.write_i32::<LittleEndian>(ent)?; // entity operand
@@ -23,5 +64,39 @@ pub fn create(
     f.write_i16::<LittleEndian>(rot)?; // rotation operand
     f.write_u8(fx as u8)?; // flipped x operand
     f.write_u8(fy as u8)?; // flipped y operand
+    f.write_i32::<LittleEndian>(ox)?; // x origin operand
+    f.write_i32::<LittleEndian>(oy)?; // y origin operand
+    Ok(())
+}
+
+pub fn set_tags<W: Write>(f: &mut W, tags: &[i32]) -> Result<()> {
+    let count = u16::try_from(tags.len())
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "entity has too many tags"))?;
+    f.write_u8(0x5)?; // entity tags instruction
+    f.write_u16::<LittleEndian>(count)?; // count operand
+    for tag in tags {
+        f.write_i32::<LittleEndian>(*tag)?; // tag operand
+    }
+    Ok(())
+}
+
+/// `borders` are the nine-slice borders, top, right, bottom then left.
+pub fn set_sprite<W: Write>(
+    f: &mut W,
+    tileset: i32,
+    (x, y, w, h): (u16, u16, u16, u16),
+    mode: u8,
+    borders: [u16; 4],
+) -> Result<()> {
+    f.write_u8(0xB)?; // entity sprite instruction
+    f.write_i32::<LittleEndian>(tileset)?; // tileset operand
+    f.write_u16::<LittleEndian>(x)?; // source x operand
+    f.write_u16::<LittleEndian>(y)?; // source y operand
+    f.write_u16::<LittleEndian>(w)?; // source width operand
+    f.write_u16::<LittleEndian>(h)?; // source height operand
+    f.write_u8(mode)?; // render mode operand
+    for border in borders.iter() {
+        f.write_u16::<LittleEndian>(*border)?; // nine-slice border operand
+    }
     Ok(())
 }
@@ -0,0 +1,361 @@
+use std::{fmt, io};
+
+/// An error raised while converting a single LDtk level into the LVL format.
+///
+/// Every error records the level it happened in, and the layer when one was being processed,
+/// so a malformed (or simply newer) project file points the user straight at the offending
+/// data instead of crashing the converter.
+#[derive(Debug)]
+pub struct ConvertError {
+    /// Identifier of the level being converted
+    pub level: String,
+
+    /// Identifier of the layer being converted, if any
+    pub layer: Option<String>,
+
+    pub kind: ErrorKind,
+}
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    /// The level has no `layerInstances`, which happens when it is stored in an external
+    /// `.ldtkl` file and only the project file was read.
+    MissingLayers,
+
+    /// The layer `__type` is not one the LVL format knows how to represent.
+    UnsupportedLayerType(String),
+
+    /// An entity instance is missing data the converter depends on.
+    MalformedEntity {
+        identifier: String,
+        iid: String,
+        field: &'static str,
+        expected: usize,
+        found: usize,
+    },
+
+    /// An entity has no field of the identifier the entity table names.
+    MissingField {
+        identifier: String,
+        iid: String,
+        field: String,
+    },
+
+    /// An entity field named by the entity table does not hold the type of value expected.
+    InvalidField {
+        identifier: String,
+        iid: String,
+        field: String,
+        expected: &'static str,
+    },
+
+    /// An entity definition uses a `tileRenderMode` the LVL format does not know.
+    UnsupportedRenderMode(String),
+
+    /// A tile instance is missing data the converter depends on.
+    MalformedTile {
+        tile: i64,
+        field: &'static str,
+        expected: usize,
+        found: usize,
+    },
+
+    /// The requested LVL format version is not one the converter can write.
+    UnsupportedFormat(u8),
+
+    /// A value does not fit in the operand type the LVL format reserves for it.
+    OutOfRange {
+        field: &'static str,
+        value: i64,
+    },
+
+    Io(io::Error),
+}
+
+impl ConvertError {
+    pub fn new(level: &str, kind: ErrorKind) -> Self {
+        ConvertError {
+            level: level.to_owned(),
+            layer: None,
+            kind,
+        }
+    }
+
+    pub fn in_layer(level: &str, layer: &str, kind: ErrorKind) -> Self {
+        ConvertError {
+            level: level.to_owned(),
+            layer: Some(layer.to_owned()),
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "level `{}`", self.level)?;
+        if let Some(layer) = &self.layer {
+            write!(f, ", layer `{}`", layer)?;
+        }
+        write!(f, ": {}", self.kind)
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::MissingLayers => write!(
+                f,
+                "no layer instances found, pass the external level file (.ldtkl) instead"
+            ),
+            ErrorKind::UnsupportedLayerType(typ) => {
+                write!(f, "unsupported layer type `{}`", typ)
+            }
+            ErrorKind::MalformedEntity {
+                identifier,
+                iid,
+                field,
+                expected,
+                found,
+            } => write!(
+                f,
+                "entity `{}` ({}) has {} value(s) in `{}`, expected {}",
+                identifier, iid, found, field, expected
+            ),
+            ErrorKind::MissingField {
+                identifier,
+                iid,
+                field,
+            } => write!(
+                f,
+                "entity `{}` ({}) has no field `{}`",
+                identifier, iid, field
+            ),
+            ErrorKind::InvalidField {
+                identifier,
+                iid,
+                field,
+                expected,
+            } => write!(
+                f,
+                "entity `{}` ({}) field `{}` does not hold {}",
+                identifier, iid, field, expected
+            ),
+            ErrorKind::UnsupportedRenderMode(mode) => {
+                write!(f, "unsupported entity tile render mode `{}`", mode)
+            }
+            ErrorKind::MalformedTile {
+                tile,
+                field,
+                expected,
+                found,
+            } => write!(
+                f,
+                "tile #{} has {} value(s) in `{}`, expected {}",
+                tile, found, field, expected
+            ),
+            ErrorKind::UnsupportedFormat(format) => {
+                write!(f, "LVL format {} is not supported", format)
+            }
+            ErrorKind::OutOfRange { field, value } => {
+                write!(f, "`{}` value {} is out of range", field, value)
+            }
+            ErrorKind::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ErrorKind::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// An error raised when a project file was saved by an LDtk version we cannot normalize.
+#[derive(Debug)]
+pub enum VersionError {
+    /// The project has no `jsonVersion`, so it is most likely not an LDtk project.
+    Missing,
+
+    Unsupported(String),
+
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for VersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use crate::compat::{CURRENT, NEWEST_MAJOR, OLDEST};
+
+        match self {
+            VersionError::Missing => write!(f, "project has no `jsonVersion`"),
+            VersionError::Unsupported(found) => write!(
+                f,
+                "LDtk JSON version `{}` is not supported, supported versions are {} through {}.x (tested with {})",
+                found, OLDEST, NEWEST_MAJOR, CURRENT
+            ),
+            VersionError::Json(_) => write!(f, "malformed LDtk file"),
+        }
+    }
+}
+
+impl std::error::Error for VersionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VersionError::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Every level that failed during a batch conversion, in project order.
+#[derive(Debug)]
+pub struct BatchError {
+    /// Number of levels the batch attempted
+    pub total: usize,
+
+    /// Identifier of each failed level, with the reason it failed
+    pub failures: Vec<(String, anyhow::Error)>,
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} levels failed to convert",
+            self.failures.len(),
+            self.total
+        )?;
+        for (level, err) in self.failures.iter() {
+            write!(f, "\n  {}: {:#}", level, err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+/// An error raised while decoding an LVL file.
+#[derive(Debug)]
+pub enum ReadError {
+    /// The file does not start with the LVL header.
+    BadHeader,
+
+    /// The file ends in the middle of the command starting at `offset`.
+    Truncated {
+        offset: u64,
+    },
+
+    UnknownCommand {
+        code: u8,
+        offset: u64,
+    },
+
+    UnknownFlag {
+        command: u8,
+        flag: u8,
+        offset: u64,
+    },
+
+    /// The string table starting at `offset` holds a string that is not valid UTF-8.
+    InvalidString {
+        offset: u64,
+    },
+
+    /// A command refers to a string the string table does not have.
+    MissingString(u32),
+
+    /// An archive has no level with this identifier or iid.
+    MissingLevel(String),
+
+    /// A Format 1 file uses header flags this reader does not know about.
+    UnsupportedFlags(u32),
+
+    /// The section directory has no entry at this index, or the file has no directory at all.
+    MissingSection(usize),
+
+    /// The command at `offset` does not end where the section it starts in does.
+    SectionOverrun {
+        index: usize,
+        offset: u64,
+    },
+
+    /// The content of a compressed file could not be inflated.
+    Decompress(io::Error),
+
+    /// The file was expected to end with a checksum command but does not.
+    MissingChecksum,
+
+    /// The content of the file does not match its checksum, it was corrupted.
+    ChecksumMismatch {
+        expected: u32,
+        found: u32,
+    },
+
+    /// Data follows the checksum command, which must be the last one of the file.
+    TrailingData {
+        offset: u64,
+    },
+
+    Io(io::Error),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::BadHeader => write!(f, "not an LVL file, header is missing"),
+            ReadError::Truncated { offset } => {
+                write!(f, "file ends in the middle of the command at {:#x}", offset)
+            }
+            ReadError::UnknownCommand { code, offset } => {
+                write!(f, "unknown command code {} at {:#x}", code, offset)
+            }
+            ReadError::InvalidString { offset } => {
+                write!(f, "string table at {:#x} is not valid UTF-8", offset)
+            }
+            ReadError::MissingString(index) => {
+                write!(f, "no string #{} in the string table", index)
+            }
+            ReadError::MissingLevel(name) => write!(f, "no level `{}` in the archive", name),
+            ReadError::UnsupportedFlags(flags) => {
+                write!(f, "unsupported header flags {:#010x}", flags)
+            }
+            ReadError::MissingSection(index) => write!(f, "no section #{}", index),
+            ReadError::SectionOverrun { index, offset } => write!(
+                f,
+                "command at {:#x} runs past the end of section #{}",
+                offset, index
+            ),
+            ReadError::Decompress(_) => write!(f, "compressed content is corrupted"),
+            ReadError::MissingChecksum => write!(f, "file has no checksum"),
+            ReadError::ChecksumMismatch { expected, found } => write!(
+                f,
+                "checksum mismatch, expected {:#010x} but content hashes to {:#010x}",
+                expected, found
+            ),
+            ReadError::TrailingData { offset } => {
+                write!(f, "unexpected data after the checksum at {:#x}", offset)
+            }
+            ReadError::UnknownFlag {
+                command,
+                flag,
+                offset,
+            } => write!(
+                f,
+                "unknown flag {} for command code {} at {:#x}",
+                flag, command, offset
+            ),
+            ReadError::Io(_) => write!(f, "failed to read input"),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadError::Io(err) | ReadError::Decompress(err) => Some(err),
+            _ => None,
+        }
+    }
+}
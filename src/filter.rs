@@ -0,0 +1,257 @@
+// rules selecting which parts of a level get converted, so editor-only data (guide layers,
+// annotations...) can stay in the project without ending up in the game
+
+use std::{fmt, str::FromStr};
+
+use {
+    anyhow::{bail, Error},
+    serde::{Deserialize, Serialize},
+};
+
+use crate::runtime::{LayerDefinition, LayerInstance, Project};
+
+/// Layer types a `type:` rule may name.
+const LAYER_TYPES: &[&str] = &["IntGrid", "Entities", "Tiles", "AutoLayer"];
+
+/// Selects the layers to convert: a layer is converted when it matches one of the `include`
+/// rules, or there are none, and none of the `exclude` rules.
+#[derive(Default, Debug, Clone, PartialEq, Serialize)]
+pub struct LayerFilter {
+    pub include: Vec<LayerRule>,
+    pub exclude: Vec<LayerRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum LayerRule {
+    /// Layer identifier, written `Name` or `identifier:Name`
+    Identifier(String),
+
+    /// Layer type, written `type:IntGrid`
+    Type(String),
+
+    /// Any of the definition's required, excluded or display tags, written `tag:editorOnly`
+    Tag(String),
+}
+
+/// Selects the entities to convert by their tags: an entity is converted when it has one of
+/// the `include_tags`, or there are none, and none of the `exclude_tags`.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityFilter {
+    #[serde(default)]
+    pub include_tags: Vec<String>,
+
+    #[serde(default)]
+    pub exclude_tags: Vec<String>,
+}
+
+impl LayerFilter {
+    /// Parses the include and exclude rules given on the command line or in the configuration.
+    pub fn parse(include: &[String], exclude: &[String]) -> Result<Self, Error> {
+        Ok(LayerFilter {
+            include: include
+                .iter()
+                .map(|rule| rule.parse())
+                .collect::<Result<_, _>>()?,
+            exclude: exclude
+                .iter()
+                .map(|rule| rule.parse())
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    pub fn includes(&self, project: &Project, layer: &LayerInstance) -> bool {
+        let def = project
+            .defs
+            .layers
+            .iter()
+            .find(|def| def.uid == layer.layer_def_uid);
+        let matches = |rule: &LayerRule| rule.matches(layer, def);
+
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+
+    /// Every rule, included or excluded.
+    pub fn rules(&self) -> impl Iterator<Item = &LayerRule> {
+        self.include.iter().chain(self.exclude.iter())
+    }
+}
+
+impl EntityFilter {
+    pub fn includes(&self, tags: &[String]) -> bool {
+        (self.include_tags.is_empty() || self.include_tags.iter().any(|tag| tags.contains(tag)))
+            && !self.exclude_tags.iter().any(|tag| tags.contains(tag))
+    }
+
+    /// Appends the rules of another filter.
+    pub fn merge(&mut self, other: EntityFilter) {
+        for tag in other.include_tags {
+            if !self.include_tags.contains(&tag) {
+                self.include_tags.push(tag);
+            }
+        }
+        for tag in other.exclude_tags {
+            if !self.exclude_tags.contains(&tag) {
+                self.exclude_tags.push(tag);
+            }
+        }
+    }
+}
+
+impl LayerRule {
+    fn matches(&self, layer: &LayerInstance, def: Option<&LayerDefinition>) -> bool {
+        match self {
+            LayerRule::Identifier(identifier) => &layer.identifier == identifier,
+            LayerRule::Type(typ) => &layer.layer_instance_type == typ,
+            LayerRule::Tag(tag) => def.map_or(false, |def| def_has_tag(def, tag)),
+        }
+    }
+
+    /// Whether layers of this definition match the rule.
+    pub fn matches_definition(&self, def: &LayerDefinition) -> bool {
+        match self {
+            LayerRule::Identifier(identifier) => &def.identifier == identifier,
+            LayerRule::Type(typ) => &def.layer_definition_type == typ,
+            LayerRule::Tag(tag) => def_has_tag(def, tag),
+        }
+    }
+}
+
+fn def_has_tag(def: &LayerDefinition, tag: &str) -> bool {
+    def.required_tags
+        .iter()
+        .chain(def.excluded_tags.iter())
+        .chain(def.ui_filter_tags.iter())
+        .any(|t| t == tag)
+}
+
+impl FromStr for LayerRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = match s.split_once(':') {
+            Some((kind, value)) => (kind, value),
+            None => ("identifier", s),
+        };
+        if value.is_empty() {
+            bail!("layer rule `{}` is empty", s);
+        }
+
+        match kind {
+            "identifier" => Ok(LayerRule::Identifier(value.to_owned())),
+            "type" if LAYER_TYPES.contains(&value) => Ok(LayerRule::Type(value.to_owned())),
+            "type" => bail!(
+                "unknown layer type `{}`, expected one of {}",
+                value,
+                LAYER_TYPES.join(", ")
+            ),
+            "tag" => Ok(LayerRule::Tag(value.to_owned())),
+            _ => bail!(
+                "unknown layer rule `{}`, expected a layer identifier, `identifier:`, `type:` or `tag:`",
+                s
+            ),
+        }
+    }
+}
+
+impl fmt::Display for LayerRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayerRule::Identifier(identifier) => write!(f, "identifier:{}", identifier),
+            LayerRule::Type(typ) => write!(f, "type:{}", typ),
+            LayerRule::Tag(tag) => write!(f, "tag:{}", tag),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::runtime::fixtures;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> LayerFilter {
+        let rules = |rules: &[&str]| {
+            rules
+                .iter()
+                .map(|rule| rule.to_string())
+                .collect::<Vec<_>>()
+        };
+        LayerFilter::parse(&rules(include), &rules(exclude)).unwrap()
+    }
+
+    #[test]
+    fn layer_rules_parse_by_their_prefix() {
+        let rule = |rule: &str| rule.parse::<LayerRule>();
+        assert_eq!(
+            rule("Walls").unwrap(),
+            LayerRule::Identifier("Walls".into())
+        );
+        assert_eq!(
+            rule("identifier:type:x").unwrap(),
+            LayerRule::Identifier("type:x".into())
+        );
+        assert_eq!(rule("type:Tiles").unwrap(), LayerRule::Type("Tiles".into()));
+        assert_eq!(rule("tag:guide").unwrap(), LayerRule::Tag("guide".into()));
+        assert_eq!(rule("tag:guide").unwrap().to_string(), "tag:guide");
+        assert!(rule("type:Walls").is_err());
+        assert!(rule("tag:").is_err());
+        assert!(rule("kind:x").is_err());
+    }
+
+    #[test]
+    fn layers_are_selected_by_identifier_type_and_tag() {
+        let mut defs = fixtures::defs();
+        defs["layers"][0]["requiredTags"] = json!(["solid"]);
+        defs["layers"][0]["excludedTags"] = json!(["editorOnly"]);
+        defs["layers"][1]["uiFilterTags"] = json!(["guide"]);
+        let project = fixtures::project(
+            defs,
+            vec![fixtures::entities(vec![]), fixtures::int_grid(2, &[1, 0])],
+        );
+        let layers = project.levels[0].layer_instances.as_ref().unwrap();
+        let included = |filter: LayerFilter| {
+            layers
+                .iter()
+                .filter(|layer| filter.includes(&project, layer))
+                .map(|layer| layer.identifier.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(included(filter(&[], &[])), ["Entities", "Grid"]);
+        assert_eq!(included(filter(&["Grid"], &[])), ["Grid"]);
+        assert_eq!(included(filter(&["type:Entities"], &[])), ["Entities"]);
+        assert_eq!(included(filter(&["tag:solid"], &[])), ["Grid"]);
+        assert_eq!(included(filter(&[], &["tag:guide"])), ["Grid"]);
+        assert_eq!(included(filter(&[], &["tag:editorOnly"])), ["Entities"]);
+        assert_eq!(
+            included(filter(&["type:IntGrid", "Entities"], &["tag:solid"])),
+            ["Entities"]
+        );
+    }
+
+    #[test]
+    fn entities_are_selected_by_tag() {
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.to_string()).collect::<Vec<_>>();
+        let mut filter = EntityFilter::default();
+        assert!(filter.includes(&[]));
+
+        filter.merge(EntityFilter {
+            include_tags: tags(&["enemy", "pickup"]),
+            exclude_tags: tags(&["editorOnly"]),
+        });
+        assert!(filter.includes(&tags(&["enemy"])));
+        assert!(!filter.includes(&tags(&["door"])));
+        assert!(!filter.includes(&tags(&["pickup", "editorOnly"])));
+
+        // merged rules add to the ones already there, once
+        filter.merge(EntityFilter {
+            include_tags: tags(&["door", "enemy"]),
+            exclude_tags: tags(&[]),
+        });
+        assert_eq!(filter.include_tags, tags(&["enemy", "pickup", "door"]));
+        assert!(filter.includes(&tags(&["door"])));
+    }
+}
@@ -1,23 +1,52 @@
-use std::fs::File;
 use std::io::{Result, Write};
 
 use byteorder::{LittleEndian, WriteBytesExt};
 
-pub fn write_header(mut f: &File) -> Result<()> {
-    f.write("LVL Format 0.".as_bytes())?;
+/// LVL format versions the converter can write.
+pub const FORMATS: &[u8] = &[0, 1];
+
+/// Format written unless another one is asked for.
+pub const DEFAULT_FORMAT: u8 = 0;
+
+/// Magic bytes every LVL Format 0 file starts with.
+pub const HEADER: &[u8] = b"LVL Format 0.";
+
+/// Magic bytes every LVL Format 1 file starts with, followed by the section directory.
+pub const HEADER_1: &[u8] = b"LVL Format 1.";
+
+pub fn write_header<W: Write>(f: &mut W) -> Result<()> {
+    f.write_all(HEADER)?;
     Ok(())
 }
 
-pub fn set_width(mut f: &File, w: u64) -> Result<()> {
+pub fn set_width<W: Write>(f: &mut W, w: u64) -> Result<()> {
     f.write_u8(0x0)?; // global properties instruction
     f.write_u8(0x0)?; // width flag
     f.write_u64::<LittleEndian>(w)?; // width operand
     Ok(())
 }
 
-pub fn set_height(mut f: &File, h: u64) -> Result<()> {
+pub fn set_height<W: Write>(f: &mut W, h: u64) -> Result<()> {
     f.write_u8(0x0)?; // global properties instruction
     f.write_u8(0x1)?; // height flag
     f.write_u64::<LittleEndian>(h)?; // height operand
     Ok(())
 }
+
+/// Names the level, `name` being an index into the string table.
+pub fn set_name<W: Write>(f: &mut W, name: u32) -> Result<()> {
+    f.write_u8(0x0)?; // global properties instruction
+    f.write_u8(0x4)?; // name flag
+    f.write_u32::<LittleEndian>(name)?; // name operand
+    Ok(())
+}
+
+/// Size of the checksum command, always the last one of the file.
+pub const CHECKSUM_SIZE: u64 = 1 + 4;
+
+/// Ends the file, `crc` being the CRC-32 of every byte written before this command.
+pub fn set_checksum<W: Write>(f: &mut W, crc: u32) -> Result<()> {
+    f.write_u8(0xFF)?; // checksum instruction
+    f.write_u32::<LittleEndian>(crc)?; // checksum operand
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use {
+    anyhow::{Context, Result},
+    serde::{Deserialize, Serialize},
+};
+
+use crate::{
+    collision::Collision, decal::Decals, filter::EntityFilter, navigation::Navigation,
+    outline::Outline,
+};
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InfoTables {
+    pub entity_table: Vec<EntityTableEntry>,
+    pub tileset_table: Vec<TilesetTableEntry>,
+
+    /// Entities to convert, by tag
+    #[serde(default)]
+    pub entity_filter: EntityFilter,
+
+    /// Tags written along with each entity, an entity's tags are only written when this is
+    /// not empty
+    #[serde(default)]
+    pub tag_table: Vec<TagTableEntry>,
+
+    /// Solid IntGrid values, merged into collision rectangles
+    #[serde(default)]
+    pub collision: Collision,
+
+    /// IntGrid values traced into outline polygons
+    #[serde(default)]
+    pub outline: Outline,
+
+    /// Walkable IntGrid values, exported as a navigation graph
+    #[serde(default)]
+    pub navigation: Navigation,
+
+    /// Entities written as decals rather than entities
+    #[serde(default)]
+    pub decals: Decals,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityTableEntry {
+    pub name: String,
+    pub value: i64,
+    pub width: i64,
+    pub height: i64,
+
+    /// Int or Float field holding the rotation in degrees, none when unset
+    #[serde(default)]
+    pub rotation_field: Option<String>,
+
+    /// Bool field flipping the entity horizontally, never flipped when unset
+    #[serde(default)]
+    pub flip_x_field: Option<String>,
+
+    /// Bool field flipping the entity vertically, never flipped when unset
+    #[serde(default)]
+    pub flip_y_field: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TilesetTableEntry {
+    pub name: String,
+    pub value: i64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagTableEntry {
+    pub name: String,
+    pub value: i64,
+}
+
+impl InfoTables {
+    /// Appends the entries of another table, entries already present win when both name the
+    /// same entity, tileset or tag. Entity filters are combined, the first collision,
+    /// outline, navigation and decal settings win.
+    pub fn merge(&mut self, other: InfoTables) {
+        for entry in other.entity_table {
+            if !self.entity_table.iter().any(|e| e.name == entry.name) {
+                self.entity_table.push(entry);
+            }
+        }
+        for entry in other.tileset_table {
+            if !self.tileset_table.iter().any(|e| e.name == entry.name) {
+                self.tileset_table.push(entry);
+            }
+        }
+        for entry in other.tag_table {
+            if !self.tag_table.iter().any(|e| e.name == entry.name) {
+                self.tag_table.push(entry);
+            }
+        }
+        self.entity_filter.merge(other.entity_filter);
+        self.collision.merge(other.collision);
+        self.outline.merge(other.outline);
+        self.navigation.merge(other.navigation);
+        self.decals.merge(other.decals);
+    }
+}
+
+pub fn read(path: &Path) -> Result<InfoTables> {
+    use std::io::Read;
+
+    let infotable_file = File::open(path)?;
+    let mut infotable_buf = BufReader::new(infotable_file);
+    let mut infotable_contents = String::new();
+    infotable_buf.read_to_string(&mut infotable_contents)?;
+    parse(&infotable_contents)
+}
+
+pub fn parse(raw: &str) -> Result<InfoTables> {
+    Ok(serde_json::from_str::<InfoTables>(raw)?)
+}
+
+/// Reads and merges several info tables, in order.
+pub fn read_all(paths: &[PathBuf]) -> Result<InfoTables> {
+    let mut tables = InfoTables::default();
+    for path in paths {
+        tables.merge(read(path).with_context(|| format!("failed to read {}", path.display()))?);
+    }
+    Ok(tables)
+}
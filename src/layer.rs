@@ -1,24 +1,31 @@
-use std::fs::File;
 use std::io::{Result, Write};
 
 use byteorder::{LittleEndian, WriteBytesExt};
 
-pub fn set_width(mut f: &File, w: u32) -> Result<()> {
+pub fn set_width<W: Write>(f: &mut W, w: u32) -> Result<()> {
     f.write_u8(0x1)?; // layer properties instruction
     f.write_u8(0x0)?; // width flag
     f.write_u32::<LittleEndian>(w)?; // width operand
     Ok(())
 }
-pub fn set_height(mut f: &File, h: u32) -> Result<()> {
+pub fn set_height<W: Write>(f: &mut W, h: u32) -> Result<()> {
     f.write_u8(0x1)?; // height properties instruction
     f.write_u8(0x1)?; // height flag
     f.write_u32::<LittleEndian>(h)?; // width operand
     Ok(())
 }
 
-pub fn set_type(mut f: &File, typ: u8) -> Result<()> {
+pub fn set_type<W: Write>(f: &mut W, typ: u8) -> Result<()> {
     f.write_u8(0x1)?; // layer properties instruction
     f.write_u8(0x4)?; // set type flag
     f.write_u8(typ)?; // type operand
     Ok(())
 }
+
+/// Names the layer, `name` being an index into the string table.
+pub fn set_name<W: Write>(f: &mut W, name: u32) -> Result<()> {
+    f.write_u8(0x1)?; // layer properties instruction
+    f.write_u8(0x5)?; // name flag
+    f.write_u32::<LittleEndian>(name)?; // name operand
+    Ok(())
+}
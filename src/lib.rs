@@ -0,0 +1,29 @@
+pub mod archive;
+pub mod batch;
+pub mod cache;
+pub mod checksum;
+pub mod collision;
+pub mod compat;
+pub mod config;
+pub mod convert;
+pub mod decal;
+pub mod entity;
+pub mod error;
+pub mod filter;
+pub mod global;
+pub mod grid;
+pub mod info;
+pub mod layer;
+pub mod ldtk;
+pub mod navigation;
+pub mod outline;
+pub mod overlay;
+pub mod reader;
+pub mod render;
+pub mod runtime;
+pub mod section;
+pub mod string;
+pub mod tile;
+pub mod validate;
+pub mod watch;
+pub mod world;
@@ -1,208 +1,1025 @@
-mod entity;
-mod global;
-mod grid;
-mod layer;
-mod ldtk;
-mod tile;
+// the binary keeps the capitalized package name
+#![allow(non_snake_case)]
 
-use std::{fs::File, io::BufReader, path::PathBuf};
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{BufReader, Cursor, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use {
-    anyhow::Result,
-    hashbrown::HashMap,
-    serde::{Deserialize, Serialize},
-    structopt::StructOpt,
+    anyhow::{Context, Result},
+    clap::{Args, CommandFactory, Parser, Subcommand},
 };
 
-use scopeguard::{defer, defer_on_unwind};
-
-use crate::ldtk::{LdtkJson, Level};
+use cartographer::{
+    archive::{self, Archive},
+    batch::{self, Outcome},
+    compat,
+    config::{self, Config},
+    convert,
+    filter::{EntityFilter, LayerFilter},
+    global, info, overlay,
+    reader::{self, Command as LvlCommand, Reader},
+    render,
+    runtime::{self, Level, Project},
+    section, validate, watch,
+    world::{self, WorldMap},
+};
 
-#[derive(StructOpt, Debug)]
-#[structopt(name = "cartographer")]
+/// Convert tilemap data into a small, compact, and fast to read, binary representation.
+#[derive(Parser, Debug)]
+#[command(name = "cartographer", version)]
 struct Cli {
-    #[structopt(short, long, parse(from_os_str))]
-    project: PathBuf,
+    /// Configuration file, `cartographer.toml` in the working directory is used when present
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Convert a single level into an LVL file, or every level when none is given
+    Convert(ConvertArgs),
+
+    /// Convert every level of a project into a directory of LVL files
+    Batch(BatchArgs),
+
+    /// Convert every level, then convert again whenever the project, one of its external
+    /// levels or an info table is saved
+    Watch {
+        #[command(flatten)]
+        batch: BatchArgs,
+
+        /// Milliseconds between two checks of the input files
+        #[arg(long, default_value_t = 500)]
+        interval: u64,
+    },
+
+    /// Pack every level of each world into a single archive file
+    Archive(ArchiveArgs),
+
+    /// Print the commands stored in an LVL file, or the index of an archive
+    Inspect {
+        file: PathBuf,
+
+        /// Print the commands of this level of an archive, by identifier or iid
+        #[arg(short, long)]
+        level: Option<String>,
+
+        /// Only print how many commands of each kind the file holds
+        #[arg(short, long)]
+        summary: bool,
+
+        /// Only read this section of a Format 1 file, seeking straight to it
+        #[arg(long)]
+        section: Option<usize>,
+    },
+
+    /// Check a project and info tables convert cleanly, without writing anything
+    Validate(Inputs),
+
+    /// Draw a level as a PNG image, from an LVL file or converted straight from the project
+    Render(RenderArgs),
+
+    /// Write an SVG of each level with labeled entity boxes, IntGrid cells and one group per
+    /// layer, for reviewing changes
+    Overlay {
+        #[arg(short, long)]
+        project: Option<PathBuf>,
+
+        /// Level to draw, either an external level file (.ldtkl) or the identifier or iid of a
+        /// level in the project, every level is drawn when omitted
+        #[arg(short, long)]
+        level: Option<String>,
+
+        /// Output file, or output directory when drawing every level, each overlay is then
+        /// named after its level. A single level drawn into a directory, or the configured
+        /// output, is named after it as well
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Write the map of each world: level positions, sizes, depths and neighbours
+    World {
+        #[arg(short, long)]
+        project: Option<PathBuf>,
+
+        /// Output directory, each map is named after its world
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Check LVL files against their checksum and decode them, reporting corrupted ones
+    Verify {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Also fail on files written without a checksum
+        #[arg(long)]
+        require: bool,
+    },
+}
+
+/// Inputs every conversion needs, falling back to the configuration file when omitted.
+#[derive(Args, Debug)]
+struct Inputs {
+    #[arg(short, long)]
+    project: Option<PathBuf>,
+
+    /// Info table, may be repeated to merge several tables in order
+    #[arg(long = "info-table")]
+    info_tables: Vec<PathBuf>,
+
+    /// Only convert the layers matching this rule, may be repeated: a layer identifier,
+    /// `type:<IntGrid|Entities|Tiles|AutoLayer>` or `tag:<tag>`
+    #[arg(long = "layer")]
+    layers: Vec<String>,
+
+    /// Leave out the layers matching this rule, may be repeated, same syntax as --layer
+    #[arg(long = "exclude-layer")]
+    exclude_layers: Vec<String>,
+
+    /// Only convert entities with this tag, may be repeated, on top of the info table's filter
+    #[arg(long = "entity-tag")]
+    entity_tags: Vec<String>,
+
+    /// Leave out entities with this tag, may be repeated
+    #[arg(long = "exclude-entity-tag")]
+    exclude_entity_tags: Vec<String>,
+
+    /// LVL format version to write: 0, a flat command stream, or 1, adding a section
+    /// directory [default: 0]
+    #[arg(long)]
+    format: Option<u8>,
+
+    /// End every level with a CRC-32 of its content, checked by `verify`
+    #[arg(long, overrides_with = "no_checksum")]
+    checksum: bool,
+
+    /// Leave the checksum out, even when the configuration file asks for it
+    #[arg(long, overrides_with = "checksum")]
+    no_checksum: bool,
+
+    /// Compress every level with DEFLATE, needs format 1
+    #[arg(long, overrides_with = "no_compress")]
+    compress: bool,
+
+    /// Leave levels uncompressed, even when the configuration file asks for compression
+    #[arg(long, overrides_with = "compress")]
+    no_compress: bool,
+
+    /// Write the name of each level and layer, through a string table
+    #[arg(long, overrides_with = "no_names")]
+    names: bool,
+
+    /// Leave names out, even when the configuration file asks for them
+    #[arg(long, overrides_with = "names")]
+    no_names: bool,
+
+    /// Position entities by their top left corner rather than their pivot
+    #[arg(long, overrides_with = "no_top_left")]
+    top_left: bool,
+
+    /// Position entities by their pivot, even when the configuration file asks for the top
+    /// left corner
+    #[arg(long, overrides_with = "top_left")]
+    no_top_left: bool,
+}
+
+#[derive(Args, Debug)]
+struct ConvertArgs {
+    #[command(flatten)]
+    inputs: Inputs,
+
+    /// Level to convert, either an external level file (.ldtkl) or the identifier or iid of
+    /// a level in the project, every level is converted when omitted
+    #[arg(short, long)]
+    level: Option<String>,
+
+    /// Output file, or output directory when converting every level. A single level written
+    /// into a directory, or the configured output, is named after the file name pattern
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    #[command(flatten)]
+    batch: BatchOptions,
+}
+
+#[derive(Args, Debug)]
+struct BatchArgs {
+    #[command(flatten)]
+    inputs: Inputs,
+
+    /// Output directory
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    #[command(flatten)]
+    batch: BatchOptions,
+}
+
+#[derive(Args, Debug)]
+struct ArchiveArgs {
+    #[command(flatten)]
+    inputs: Inputs,
 
-    #[structopt(short, long, parse(from_os_str))]
-    level: PathBuf,
+    /// Output directory, each archive is named after its world
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct RenderArgs {
+    #[command(flatten)]
+    inputs: Inputs,
+
+    /// LVL file to draw, the level is converted from the project when omitted
+    file: Option<PathBuf>,
 
-    #[structopt(short, long, parse(from_os_str))]
+    /// Level to draw, either an external level file (.ldtkl) or the identifier or iid of a
+    /// level in the project, also sets the size and background of the image
+    #[arg(short, long)]
+    level: Option<String>,
+
+    /// PNG file to write
+    #[arg(short, long)]
     output: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct BatchOptions {
+    /// Number of levels to convert concurrently, defaults to the number of cores
+    #[arg(short, long)]
+    jobs: Option<usize>,
 
-    #[structopt(long, parse(from_os_str))]
-    info_table: PathBuf,
+    /// Convert every level, even those whose inputs are unchanged since the last run
+    #[arg(short, long)]
+    force: bool,
+
+    /// Output file name of each level, made of `{identifier}`, `{iid}`, `{uid}` and `{index}`
+    /// placeholders [default: {identifier}.lvl]
+    #[arg(long)]
+    filename: Option<String>,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct InfoTables {
-    pub entity_table: Vec<EntityTableEntry>,
-    pub tileset_table: Vec<TilesetTableEntry>,
+/// The project given on the command line, otherwise the one set in the configuration.
+fn project_path<'a>(given: Option<&'a PathBuf>, config: &'a Config) -> Result<&'a PathBuf> {
+    given
+        .or(config.project.as_ref())
+        .with_context(|| missing("project", "--project"))
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct EntityTableEntry {
-    pub name: String,
-    pub value: i64,
-    pub width: i64,
-    pub height: i64,
+/// The output given on the command line, otherwise the one set in the configuration.
+fn output_path<'a>(given: Option<&'a PathBuf>, config: &'a Config) -> Result<&'a PathBuf> {
+    given
+        .or(config.output.as_ref())
+        .with_context(|| missing("output", "--output"))
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct TilesetTableEntry {
-    pub name: String,
-    pub value: i64,
+/// A switch of the configuration file, unless turned on or off on the command line.
+fn switch(on: bool, off: bool, config: bool) -> bool {
+    (config || on) && !off
 }
 
-pub fn main() -> Result<()> {
-    use std::io::Read;
+/// Error message for a setting given neither on the command line nor in the configuration.
+fn missing(setting: &str, flag: &str) -> String {
+    format!(
+        "no {} given, pass {} or set `{}` in {}",
+        setting,
+        flag,
+        setting,
+        config::FILE_NAME
+    )
+}
+
+/// Command line arguments merged over the configuration file.
+struct Settings {
+    project: PathBuf,
+    info_tables: Vec<PathBuf>,
+    output: Option<PathBuf>,
+
+    /// The output comes from the configuration, which names a directory shared by every command
+    configured_output: bool,
+
+    filename: String,
+    convert: convert::Options,
+}
+
+impl Settings {
+    fn new(
+        config: &Config,
+        inputs: &Inputs,
+        output: Option<&PathBuf>,
+        filename: Option<&String>,
+    ) -> Result<Self> {
+        let format = inputs
+            .format
+            .or(config.format)
+            .unwrap_or(global::DEFAULT_FORMAT);
+        if !global::FORMATS.contains(&format) {
+            anyhow::bail!(
+                "LVL format {} is not supported, supported formats are {:?}",
+                format,
+                global::FORMATS
+            );
+        }
+        let compress = switch(inputs.compress, inputs.no_compress, config.compress);
+        if compress && format == 0 {
+            anyhow::bail!("LVL format 0 cannot be compressed, pass --format 1 as well");
+        }
 
+        let project = project_path(inputs.project.as_ref(), config)?;
+
+        let info_tables = if inputs.info_tables.is_empty() {
+            &config.info_tables
+        } else {
+            &inputs.info_tables
+        };
+        if info_tables.is_empty() {
+            anyhow::bail!(
+                "no info table given, pass --info-table or set `info_tables` in {}",
+                config::FILE_NAME
+            );
+        }
+
+        let layers = if inputs.layers.is_empty() && inputs.exclude_layers.is_empty() {
+            LayerFilter::parse(&config.layers, &config.exclude_layers)
+        } else {
+            LayerFilter::parse(&inputs.layers, &inputs.exclude_layers)
+        };
+
+        let entities = if inputs.entity_tags.is_empty() && inputs.exclude_entity_tags.is_empty() {
+            EntityFilter {
+                include_tags: config.entity_tags.clone(),
+                exclude_tags: config.exclude_entity_tags.clone(),
+            }
+        } else {
+            EntityFilter {
+                include_tags: inputs.entity_tags.clone(),
+                exclude_tags: inputs.exclude_entity_tags.clone(),
+            }
+        };
+
+        Ok(Settings {
+            project: project.clone(),
+            info_tables: info_tables.clone(),
+            output: output.or(config.output.as_ref()).cloned(),
+            configured_output: output.is_none() && config.output.is_some(),
+            filename: filename
+                .or(config.filename.as_ref())
+                .map_or(batch::DEFAULT_FILENAME, |filename| filename.as_str())
+                .to_owned(),
+            convert: convert::Options {
+                layers: layers?,
+                entities,
+                format,
+                checksum: switch(inputs.checksum, inputs.no_checksum, config.checksum),
+                compress,
+                names: switch(inputs.names, inputs.no_names, config.names),
+                top_left: switch(inputs.top_left, inputs.no_top_left, config.top_left),
+            },
+        })
+    }
+
+    fn output(&self) -> Result<&Path> {
+        self.output
+            .as_deref()
+            .with_context(|| missing("output", "--output"))
+    }
+
+    /// Output file of a single level: the output itself, unless it is a directory, the level
+    /// is then named after the file name pattern within it.
+    fn level_output(&self, level: &Level, index: usize) -> Result<PathBuf> {
+        let output = self.output()?;
+        if self.configured_output || output.is_dir() {
+            batch::check_filename(&self.filename)?;
+            Ok(output.join(batch::output_name(&self.filename, level, index)))
+        } else {
+            Ok(output.to_owned())
+        }
+    }
+
+    fn batch(&self, options: &BatchOptions) -> batch::Options {
+        batch::Options {
+            jobs: options
+                .jobs
+                .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get())),
+            force: options.force,
+            filename: self.filename.clone(),
+            convert: self.convert.clone(),
+        }
+    }
+}
+
+pub fn main() -> Result<()> {
     // read cli arguments
-    let opt = Cli::from_args();
+    let opt = Cli::parse_from(args());
+    let config = || match &opt.config {
+        Some(path) => Config::read(path),
+        None => Ok(Config::find(Path::new("."))?.unwrap_or_default()),
+    };
+
+    match &opt.command {
+        Command::Convert(args) => {
+            let settings = Settings::new(
+                &config()?,
+                &args.inputs,
+                args.output.as_ref(),
+                args.batch.filename.as_ref(),
+            )?;
+            match &args.level {
+                Some(level) => convert(&settings, level),
+                None => run_batch(&settings, &args.batch),
+            }
+        }
 
+        Command::Batch(args) => {
+            let settings = Settings::new(
+                &config()?,
+                &args.inputs,
+                args.output.as_ref(),
+                args.batch.filename.as_ref(),
+            )?;
+            run_batch(&settings, &args.batch)
+        }
+
+        Command::Watch { batch, interval } => {
+            let settings = Settings::new(
+                &config()?,
+                &batch.inputs,
+                batch.output.as_ref(),
+                batch.batch.filename.as_ref(),
+            )?;
+            watch::run(
+                &settings.project,
+                &settings.info_tables,
+                settings.output()?,
+                &settings.batch(&batch.batch),
+                Duration::from_millis(*interval),
+                |result| match result {
+                    // only report what changed, the rest is noise in a long running session
+                    Ok(outcomes) => print_outcomes(&outcomes, false),
+                    Err(err) => eprintln!("Error: {:?}", err),
+                },
+            )
+        }
+
+        Command::Inspect {
+            file,
+            summary,
+            section,
+            level,
+        } => inspect(file, *summary, *section, level.as_deref()),
+
+        Command::Archive(args) => {
+            let settings = Settings::new(&config()?, &args.inputs, args.output.as_ref(), None)?;
+            let paths = archive::run(
+                &settings.project,
+                &settings.info_tables,
+                settings.output()?,
+                &settings.convert,
+            )?;
+            for path in paths {
+                println!("{}", path.display());
+            }
+            Ok(())
+        }
+
+        Command::Validate(inputs) => {
+            let settings = Settings::new(&config()?, inputs, None, None)?;
+            let report =
+                validate::project(&settings.project, &settings.info_tables, &settings.convert)?;
+            for warning in report.warnings.iter() {
+                println!("warning: {}", warning);
+            }
+            for error in report.errors.iter() {
+                println!("error: {:#}", error);
+            }
+            println!(
+                "{} level(s) checked, {} error(s), {} warning(s)",
+                report.levels,
+                report.errors.len(),
+                report.warnings.len()
+            );
+            if !report.is_ok() {
+                anyhow::bail!("{} is not valid", settings.project.display());
+            }
+            Ok(())
+        }
+
+        Command::Verify { files, require } => verify(files, *require),
+
+        Command::Render(args) => {
+            let settings = Settings::new(&config()?, &args.inputs, None, None)?;
+            render_level(
+                &settings,
+                args.file.as_deref(),
+                args.level.as_deref(),
+                &args.output,
+            )
+        }
+
+        Command::Overlay {
+            project,
+            level,
+            output,
+        } => {
+            let config = config()?;
+            let project = project_path(project.as_ref(), &config)?;
+            let configured_output = output.is_none();
+            let output = output_path(output.as_ref(), &config)?;
+            match level {
+                Some(level) => {
+                    let raw = fs::read_to_string(project)
+                        .with_context(|| format!("failed to read {}", project.display()))?;
+                    let parsed: Project = compat::parse_project(&raw)?;
+                    let map = load_level(project, &parsed, level)?;
+
+                    // the configured output is the directory every overlay goes to
+                    let output = if configured_output || output.is_dir() {
+                        fs::create_dir_all(output)
+                            .with_context(|| format!("failed to create {}", output.display()))?;
+                        output.join(format!("{}.{}", map.identifier, overlay::EXTENSION))
+                    } else {
+                        output.to_owned()
+                    };
+                    batch::write_atomic(&output, |mut dst| {
+                        overlay::write(&mut dst, &parsed, &map)
+                            .with_context(|| format!("failed to write {}", output.display()))
+                    })?;
+                    println!("{}", output.display());
+                }
+                None => {
+                    for path in overlay::run(project, output)? {
+                        println!("{}", path.display());
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        Command::World { project, output } => {
+            let config = config()?;
+            let project = project_path(project.as_ref(), &config)?;
+            let output = output_path(output.as_ref(), &config)?;
+            for path in world::run(project, output)? {
+                println!("{}", path.display());
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Command line arguments, invocations from before the subcommands existed (flags only) are
+/// treated as `convert` so existing scripts keep working.
+fn args() -> Vec<OsString> {
+    let mut args = std::env::args_os().collect::<Vec<_>>();
+    let command = Cli::command();
+    let has_subcommand = args.iter().skip(1).any(|arg| {
+        command
+            .get_subcommands()
+            .any(|subcommand| arg.to_str() == Some(subcommand.get_name()))
+    });
+    let legacy = !has_subcommand
+        && args.get(1).map_or(false, |first| {
+            let first = first.to_string_lossy();
+            first.starts_with('-') && !["-h", "--help", "-V", "--version"].contains(&first.as_ref())
+        });
+    if legacy {
+        args.insert(1, "convert".into());
+    }
+    args
+}
+
+fn run_batch(settings: &Settings, options: &BatchOptions) -> Result<()> {
+    let outcomes = batch::run(
+        &settings.project,
+        &settings.info_tables,
+        settings.output()?,
+        &settings.batch(options),
+    )?;
+    print_outcomes(&outcomes, true);
+    Ok(())
+}
+
+fn convert(settings: &Settings, level: &str) -> Result<()> {
     // buffered file reading
-    let src = File::open(opt.project)?;
+    let src = File::open(&settings.project)
+        .with_context(|| format!("failed to read {}", settings.project.display()))?;
     let mut buf = BufReader::new(src);
     let mut con = String::new();
 
     // marshal into a data structure
     buf.read_to_string(&mut con)?;
-    let project: LdtkJson = serde_json::from_str(&con)?;
+    let project: Project = compat::parse_project(&con)?;
 
-    let src = File::open(opt.level)?;
-    let mut buf = BufReader::new(src);
-    let mut con = String::new();
-    buf.read_to_string(&mut con)?;
-    let map: Level = serde_json::from_str(&con)?;
-
-    // read infotable
-    let infotable_file = File::open(opt.info_table)?;
-    let mut infotable_buf = BufReader::new(infotable_file);
-    let mut infotable_contents = String::new();
-    infotable_buf.read_to_string(&mut infotable_contents)?;
-    let infotable = serde_json::from_str::<InfoTables>(&infotable_contents)?;
-
-    let ent_table = infotable.entity_table;
-    let ts_table = infotable.tileset_table;
-
-    // open a new file
-    let dst = File::create(opt.output)?;
-
-    // write file header
-    global::write_header(&dst)?;
-
-    // global level properties
-    use std::convert::TryInto;
-    global::set_width(
-        &dst,
-        project
-            .default_level_width
-            .unwrap_or_else(|| 0)
-            .try_into()?,
-    )?;
-    global::set_height(
-        &dst,
-        project
-            .default_level_height
-            .unwrap_or_else(|| 0)
-            .try_into()?,
+    // read infotables
+    let infotable = info::read_all(&settings.info_tables)?;
+
+    let map = load_level(&settings.project, &project, level)?;
+    let output = settings.level_output(&map, level_index(&project, &map))?;
+
+    // patterns may place levels in sub directories
+    if let Some(dir) = output.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    batch::write_atomic(&output, |mut dst| {
+        convert::level(&project, &map, &infotable, &settings.convert, &mut dst)?;
+        Ok(())
+    })
+}
+
+/// A level file on disk, or one named in the project which may itself be external.
+fn load_level(project_path: &Path, project: &Project, level: &str) -> Result<Level> {
+    let path = Path::new(level);
+    if path.is_file() {
+        let con = fs::read_to_string(path)?;
+        return Ok(compat::parse_level(&con, project)?);
+    }
+
+    let map = project
+        .levels
+        .iter()
+        .chain(project.worlds.iter().flat_map(|world| world.levels.iter()))
+        .find(|map| map.identifier == level || map.iid == level)
+        .with_context(|| {
+            format!(
+                "`{}` is neither a level file nor a level of the project",
+                level
+            )
+        })?;
+    Ok(runtime::load_level(project_path, project, map)?.into_owned())
+}
+
+/// Position of a level in project order, root levels first, as used by `{index}` in file
+/// name patterns. Level files that are not part of the project get 0.
+fn level_index(project: &Project, level: &Level) -> usize {
+    project
+        .levels
+        .iter()
+        .chain(project.worlds.iter().flat_map(|world| world.levels.iter()))
+        .position(|map| map.iid == level.iid)
+        .unwrap_or(0)
+}
+
+fn render_level(
+    settings: &Settings,
+    file: Option<&Path>,
+    level: Option<&str>,
+    output: &Path,
+) -> Result<()> {
+    let raw = fs::read_to_string(&settings.project)
+        .with_context(|| format!("failed to read {}", settings.project.display()))?;
+    let project: Project = compat::parse_project(&raw)?;
+    let tables = info::read_all(&settings.info_tables)?;
+    let map = level
+        .map(|level| load_level(&settings.project, &project, level))
+        .transpose()?;
+
+    let (data, drawn) = match (file, &map) {
+        (Some(file), _) => {
+            let data =
+                fs::read(file).with_context(|| format!("failed to read {}", file.display()))?;
+            (data, None)
+        }
+        (None, Some(map)) => {
+            // tile layers are drawn straight from the level, the others are converted with
+            // their names, to tell where the tile layers go among them
+            let mut drawn = map.clone();
+            let mut converted = map.clone();
+            if let (Some(drawn), Some(converted)) =
+                (&mut drawn.layer_instances, &mut converted.layer_instances)
+            {
+                drawn.retain(|layer| settings.convert.layers.includes(&project, layer));
+                converted.retain(|layer| {
+                    let typ = layer.layer_instance_type.as_str();
+                    typ != "Tiles" && typ != "AutoLayer"
+                });
+            }
+            let options = convert::Options {
+                names: true,
+                ..settings.convert.clone()
+            };
+            let mut data = Vec::new();
+            convert::level(&project, &converted, &tables, &options, &mut data)?;
+            (data, Some(drawn))
+        }
+        (None, None) => anyhow::bail!("nothing to render, pass an LVL file or --level"),
+    };
+    let commands = Reader::new(Cursor::new(data)).context("failed to read the level")?;
+
+    let size = map
+        .as_ref()
+        .map(|map| (map.px_wid.max(0) as u32, map.px_hei.max(0) as u32));
+    let background = map
+        .as_ref()
+        .map_or(&project.default_level_bg_color, |map| &map.bg_color);
+    let dir = settings.project.parent().unwrap_or_else(|| Path::new("."));
+    let image = render::level(
+        commands,
+        &project,
+        &tables,
+        dir,
+        size,
+        background,
+        drawn.as_ref(),
     )?;
+    render::save(&image, output)
+}
 
-    // iterate through layers
-    let layers = map.layer_instances.unwrap();
-    for layer in layers.iter() {
-        // signal layer type
-        match layer.layer_instance_type.as_ref() {
-            "IntGrid" => {
-                layer::set_type(&dst, 1)?;
+fn inspect(path: &Path, summary: bool, section: Option<usize>, level: Option<&str>) -> Result<()> {
+    let context = || format!("failed to read {}", path.display());
+    let mut src = BufReader::new(File::open(path).with_context(context)?);
+
+    if starts_with(&mut src, world::HEADER).with_context(context)? {
+        if level.is_some() || section.is_some() {
+            anyhow::bail!(
+                "{} is a world map, it has no levels or sections",
+                path.display()
+            );
+        }
+        let map = WorldMap::read(src).with_context(context)?;
+        println!(
+            "# world `{}`, layout {}, grid {}x{}, {} level(s)",
+            map.identifier,
+            world::layout_name(map.layout),
+            map.grid_width,
+            map.grid_height,
+            map.levels.len()
+        );
+        for level in map.levels.iter() {
+            println!(
+                "{} ({}) at ({}, {}) depth {} size {}x{}",
+                level.identifier,
+                level.iid,
+                level.world_x,
+                level.world_y,
+                level.world_depth,
+                level.width,
+                level.height
+            );
+            for neighbour in level.neighbours.iter() {
+                let name = map
+                    .levels
+                    .get(neighbour.level as usize)
+                    .map_or("?", |level| level.identifier.as_str());
+                println!("  {}: {}", world::direction_name(neighbour.direction), name);
             }
+        }
+        return Ok(());
+    }
+
+    if !starts_with(&mut src, archive::HEADER).with_context(context)? {
+        if level.is_some() {
+            anyhow::bail!("{} is not an archive, --level needs one", path.display());
+        }
+        let commands = Reader::new(src).with_context(context)?;
+        return inspect_level(path, commands, summary, section);
+    }
 
-            "Entities" => {
-                layer::set_type(&dst, 3)?;
+    let mut archive = Archive::new(src).with_context(context)?;
+    match level {
+        Some(level) => {
+            let commands = archive.open_level(level).with_context(context)?;
+            inspect_level(path, commands, summary, section)
+        }
+        None => {
+            println!(
+                "# archive of world `{}`, {} level(s)",
+                archive.world(),
+                archive.entries().len()
+            );
+            for entry in archive.entries() {
+                println!(
+                    "{} ({}) at ({}, {}) depth {} size {}x{}, {:#08x}, {} bytes",
+                    entry.identifier,
+                    entry.iid,
+                    entry.world_x,
+                    entry.world_y,
+                    entry.world_depth,
+                    entry.width,
+                    entry.height,
+                    entry.offset,
+                    entry.length
+                );
             }
+            Ok(())
+        }
+    }
+}
+
+/// Tells the kinds of files apart by their header, leaving the source at its start.
+fn starts_with<R: Read + Seek>(src: &mut R, magic: &[u8]) -> std::io::Result<bool> {
+    let mut header = vec![0; magic.len()];
+    let matches = src.read_exact(&mut header).is_ok() && header == magic;
+    src.seek(SeekFrom::Start(0))?;
+    Ok(matches)
+}
 
-            &_ => {
-                panic!("unsupported layer type")
+fn inspect_level<R: Read + Seek + 'static>(
+    path: &Path,
+    mut commands: Reader<R>,
+    summary: bool,
+    section: Option<usize>,
+) -> Result<()> {
+    let context = || format!("failed to read {}", path.display());
+
+    if !summary {
+        println!("# format {}", commands.format());
+        for (flag, name) in [
+            (section::FLAG_CHECKSUM, "checksum"),
+            (section::FLAG_DEFLATE, "compressed"),
+        ] {
+            if commands.flags() & flag != 0 {
+                println!("# {}", name);
             }
         }
+        for (index, entry) in commands.sections().iter().enumerate() {
+            println!(
+                "# section {}: {} at {:#08x}, {} bytes",
+                index, entry, entry.offset, entry.length
+            );
+        }
+    }
 
-        // set width and height
-        layer::set_width(&dst, layer.grid_size.try_into()?)?;
-        layer::set_height(&dst, layer.grid_size.try_into()?)?;
+    // (offset, command), offsets are only known when reading the whole file
+    let decoded: Box<dyn Iterator<Item = Result<(Option<u64>, LvlCommand)>>> = match section {
+        Some(index) => Box::new(
+            commands
+                .read_section(index)
+                .with_context(context)?
+                .into_iter()
+                .map(|command| Ok((None, command))),
+        ),
+        None => Box::new(std::iter::from_fn(move || {
+            let offset = commands.offset();
+            commands
+                .next_command()
+                .with_context(context)
+                .transpose()
+                .map(|command| command.map(|command| (Some(offset), command)))
+        })),
+    };
 
-        if layer.layer_instance_type == String::from("IntGrid") {
-            // keep track of the y axis manually
-            let (mut cur_x, mut cur_y) = (0, 0);
-            let grid = &layer.int_grid_csv;
-            // grid cells
-            for cell in grid.iter() {
-                if cur_x == layer.c_wid {
-                    cur_x = 0; // reset x cursor
-                    cur_y += 1;
-                }
+    let mut counts = [0usize; 13];
+    let mut layers = 0;
+    let mut checksum = None;
+    let mut strings = Vec::new();
+    for decoded in decoded {
+        let (offset, command) = decoded?;
 
-                grid::cell_set(&dst, cur_x as i16, cur_y as i16, *cell as i8).unwrap();
-            }
-        }
-
-        // entities
-        let entities = &layer.entity_instances;
-        for entity in entities.iter() {
-            for entry in ent_table.iter() {
-                if entity.identifier.eq(entry.name.as_str()) {
-                    // DANGER: px should always be populated with two entries
-                    // NOTE: [x,y] are effected by optional layer offsets
-                    // which just aren't taken into account here at all
-                    let x = unsafe { entity.px.get_unchecked(0) };
-                    let y = unsafe { entity.px.get_unchecked(1) };
-                    let w = entity.width;
-                    let h = entity.height;
-                    let rot = 0;
-                    let flipped_x = false;
-                    let flipped_y = false;
-                    entity::create(
-                        &dst,
-                        entry.value as i32,
-                        *x as i32,
-                        *y as i32,
-                        w as u32,
-                        h as u32,
-                        rot as i16,
-                        flipped_x,
-                        flipped_y,
-                    )?;
+        if summary {
+            let code = match command {
+                LvlCommand::GlobalWidth(_)
+                | LvlCommand::GlobalHeight(_)
+                | LvlCommand::GlobalName(_) => 0,
+                LvlCommand::LayerType(_) => {
+                    layers += 1;
+                    1
+                }
+                LvlCommand::LayerWidth(_)
+                | LvlCommand::LayerHeight(_)
+                | LvlCommand::LayerName(_) => 1,
+                LvlCommand::GridCell { .. } => 2,
+                LvlCommand::Entity { .. } => 3,
+                LvlCommand::Tile { .. } => 4,
+                LvlCommand::EntityTags(_) => 5,
+                LvlCommand::StringTable(_) => 6,
+                LvlCommand::CollisionRect { .. } => 7,
+                LvlCommand::Outline { .. } => 8,
+                LvlCommand::Platform { .. } => 9,
+                LvlCommand::Link { .. } => 10,
+                LvlCommand::EntitySprite { .. } => 11,
+                LvlCommand::Decal { .. } => 12,
+                LvlCommand::Checksum(crc) => {
+                    checksum = Some(crc);
+                    continue;
                 }
+            };
+            counts[code] += 1;
+        } else {
+            if let LvlCommand::LayerType(typ) = command {
+                println!("# layer {} ({})", layers, reader::layer_type_name(typ));
+                layers += 1;
             }
+            // names are resolved against the string table, when it was read
+            let name = match &command {
+                LvlCommand::StringTable(table) => {
+                    strings = table.clone();
+                    None
+                }
+                LvlCommand::GlobalName(index) | LvlCommand::LayerName(index) => {
+                    strings.get(*index as usize)
+                }
+                _ => None,
+            };
+            let name = name
+                .map(|name| format!(" ({:?})", name))
+                .unwrap_or_default();
+            match offset {
+                Some(offset) => println!("{:#08x}  {}{}", offset, command, name),
+                None => println!("{}{}", command, name),
+            }
+        }
+    }
+
+    if summary {
+        println!("layers: {}", layers);
+        for (name, count) in [
+            "global properties",
+            "layer properties",
+            "grid cells",
+            "entities",
+            "tiles",
+            "entity tags",
+            "string tables",
+            "collision rectangles",
+            "outlines",
+            "platforms",
+            "navigation links",
+            "entity sprites",
+            "decals",
+        ]
+        .iter()
+        .zip(counts.iter())
+        {
+            println!("{}: {}", name, count);
         }
+        if let Some(crc) = checksum {
+            println!("checksum: {:#010x}", crc);
+        }
+    }
+    Ok(())
+}
 
-        // tiles
-        for tile in layer.grid_tiles.iter() {
-            for entry in ts_table.iter() {
-                // TODO: use tileset_def_uid & override_tileset_uid instead
-                let tileset_name = layer.tileset_rel_path.clone().unwrap_or_else(|| String::new());
-                if tileset_name.eq(entry.name.as_str()) {
-                    // NOTE: same as above, optional layer offsets
-                    // aren't taken into account here
-                    let x = unsafe { tile.px.get_unchecked(0) };
-                    let y = unsafe { tile.px.get_unchecked(1) };
-                    let src_x = unsafe { tile.src.get_unchecked(0) };
-                    let src_y = unsafe { tile.src.get_unchecked(1) };
-                    tile::new(
-                        &dst,
-                        entry.value as i32,
-                        *x as u32,
-                        *y as u32,
-                        *src_x as u16,
-                        *src_y as u16,
-                    )?;
+fn verify(paths: &[PathBuf], require: bool) -> Result<()> {
+    let mut failed = 0;
+    let mut total = 0;
+    for path in paths {
+        // every level of an archive is verified on its own
+        let levels = fs::read(path)
+            .with_context(|| format!("failed to read {}", path.display()))
+            .and_then(|data| {
+                if !data.starts_with(archive::HEADER) {
+                    return Ok(vec![(path.display().to_string(), data)]);
+                }
+                let mut archive = Archive::new(Cursor::new(&data))?;
+                (0..archive.entries().len())
+                    .map(|index| {
+                        let entry = archive.entries()[index].clone();
+                        let data = archive.read_level(&entry.iid)?;
+                        Ok((format!("{} ({})", path.display(), entry.identifier), data))
+                    })
+                    .collect::<Result<Vec<_>>>()
+            });
+        let levels = match levels {
+            Ok(levels) => levels,
+            Err(err) => {
+                println!("{}: {:#}", path.display(), err);
+                failed += 1;
+                total += 1;
+                continue;
+            }
+        };
+
+        for (name, data) in levels {
+            total += 1;
+            let result = reader::verify(&data).and_then(|checksum| {
+                // the checksum only vouches for the bytes, make sure they also decode
+                reader::read_all(data.as_slice())?;
+                Ok(checksum)
+            });
+            match result {
+                Ok(Some(crc)) => println!("{}: ok ({:#010x})", name, crc),
+                Ok(None) if require => {
+                    println!("{}: no checksum", name);
+                    failed += 1;
+                }
+                Ok(None) => println!("{}: ok (no checksum)", name),
+                Err(err) => {
+                    println!("{}: {:#}", name, err);
+                    failed += 1;
                 }
             }
         }
     }
+    if failed > 0 {
+        anyhow::bail!("{} of {} level(s) failed verification", failed, total);
+    }
+    Ok(())
+}
 
-    // sync to disk
-    Ok(dst.sync_data()?)
+fn print_outcomes(outcomes: &[Outcome], skipped: bool) {
+    for outcome in outcomes {
+        match outcome {
+            Outcome::Converted(path) => println!("{}", path.display()),
+            Outcome::Skipped(path) if skipped => println!("{} (up to date)", path.display()),
+            Outcome::Skipped(_) => {}
+        }
+    }
 }
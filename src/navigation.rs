@@ -0,0 +1,292 @@
+// precomputes where platformer enemies can stand and how they get from one platform to the
+// next, so AI only has to search a small graph instead of the whole grid
+
+use std::io::{Result, Write};
+
+use {
+    byteorder::{LittleEndian, WriteBytesExt},
+    serde::{Deserialize, Serialize},
+};
+
+/// Link to a platform at the same height or above, reached by jumping.
+pub const JUMP: u8 = 0;
+
+/// Link to a lower platform, reached by stepping or jumping off an edge.
+pub const FALL: u8 = 1;
+
+/// Which IntGrid values can be walked on, as set in the `navigation` object of the info table.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Navigation {
+    /// IntGrid values that can be stood on, nothing is exported when empty
+    #[serde(default)]
+    pub values: Vec<i64>,
+
+    /// IntGrid layers to export, by identifier, every IntGrid layer when empty
+    #[serde(default)]
+    pub layers: Vec<String>,
+
+    /// How many cells high a jump reaches
+    #[serde(default)]
+    pub jump_height: u32,
+
+    /// How many cells a jump or fall clears sideways
+    #[serde(default)]
+    pub jump_distance: u32,
+}
+
+/// A horizontal run of cells an agent can stand in, in cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Platform {
+    /// Leftmost cell of the run
+    pub x: usize,
+    pub y: usize,
+    pub length: usize,
+
+    /// Connected component, platforms of different components cannot reach each other
+    pub component: usize,
+}
+
+/// A way from one platform to another, by index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Link {
+    pub from: usize,
+    pub to: usize,
+
+    /// `JUMP` or `FALL`
+    pub kind: u8,
+}
+
+impl Navigation {
+    /// Whether this IntGrid layer is exported.
+    pub fn applies_to(&self, layer: &str) -> bool {
+        !self.values.is_empty()
+            && (self.layers.is_empty() || self.layers.iter().any(|name| name == layer))
+    }
+
+    pub fn is_walkable(&self, value: i64) -> bool {
+        self.values.contains(&value)
+    }
+
+    /// Takes the settings of another table, unless this one already has walkable values.
+    pub fn merge(&mut self, other: Navigation) {
+        if self.values.is_empty() {
+            *self = other;
+        }
+    }
+
+    /// Finds the platforms of a grid, left to right and top to bottom like `__intGridCsv`,
+    /// links them, then groups them into connected components.
+    ///
+    /// A cell can be stood in when it is not walkable itself and the cell below it is. Links
+    /// only consider distances, not what lies in the way of the jump, which games are left to
+    /// check against the grid.
+    pub fn graph(&self, cells: &[i64], width: usize) -> (Vec<Platform>, Vec<Link>) {
+        let mut platforms = self.platforms(cells, width);
+        let links = self.links(&platforms);
+        components(&mut platforms, &links);
+        (platforms, links)
+    }
+
+    fn platforms(&self, cells: &[i64], width: usize) -> Vec<Platform> {
+        let mut platforms = Vec::new();
+        if width == 0 {
+            return platforms;
+        }
+        let height = (cells.len() + width - 1) / width;
+        let walkable = |x: usize, y: usize| {
+            let index = y * width + x;
+            index < cells.len() && self.is_walkable(cells[index])
+        };
+        let standable = |x, y| y + 1 < height && !walkable(x, y) && walkable(x, y + 1);
+
+        for y in 0..height {
+            let mut x = 0;
+            while x < width {
+                if !standable(x, y) {
+                    x += 1;
+                    continue;
+                }
+                let start = x;
+                while x < width && standable(x, y) {
+                    x += 1;
+                }
+                platforms.push(Platform {
+                    x: start,
+                    y,
+                    length: x - start,
+                    component: 0,
+                });
+            }
+        }
+        platforms
+    }
+
+    fn links(&self, platforms: &[Platform]) -> Vec<Link> {
+        let reach = self.jump_distance as usize;
+        let mut links = Vec::new();
+        for (from, a) in platforms.iter().enumerate() {
+            let (a_left, a_right) = (a.x, a.x + a.length - 1);
+            for (to, b) in platforms.iter().enumerate() {
+                let (b_left, b_right) = (b.x, b.x + b.length - 1);
+                if from == to {
+                    continue;
+                }
+
+                let kind = if b.y <= a.y {
+                    // columns between both platforms
+                    let gap = if b_left > a_right {
+                        b_left - a_right - 1
+                    } else if a_left > b_right {
+                        a_left - b_right - 1
+                    } else {
+                        0
+                    };
+                    if a.y - b.y > self.jump_height as usize || gap > reach {
+                        continue;
+                    }
+                    JUMP
+                } else {
+                    // landing right past either edge, up to a jump away
+                    let right = b_right > a_right && b_left <= a_right + 1 + reach;
+                    let left = b_left < a_left && b_right + 1 + reach >= a_left;
+                    if !right && !left {
+                        continue;
+                    }
+                    FALL
+                };
+                links.push(Link { from, to, kind });
+            }
+        }
+        links
+    }
+}
+
+/// Numbers the connected components of the platforms in order of first appearance, following
+/// links both ways.
+fn components(platforms: &mut [Platform], links: &[Link]) {
+    let mut parent = (0..platforms.len()).collect::<Vec<_>>();
+    fn root(parent: &mut [usize], mut index: usize) -> usize {
+        while parent[index] != index {
+            parent[index] = parent[parent[index]];
+            index = parent[index];
+        }
+        index
+    }
+    for link in links {
+        let (a, b) = (root(&mut parent, link.from), root(&mut parent, link.to));
+        parent[a.max(b)] = a.min(b);
+    }
+
+    let mut ids = vec![None; platforms.len()];
+    let mut next = 0;
+    for (index, platform) in platforms.iter_mut().enumerate() {
+        let root = root(&mut parent, index);
+        platform.component = *ids[root].get_or_insert_with(|| {
+            next += 1;
+            next - 1
+        });
+    }
+}
+
+pub fn platform<W: Write>(f: &mut W, x: i16, y: i16, length: u16, component: u16) -> Result<()> {
+    f.write_u8(0x9)?; // platform instruction
+    f.write_i16::<LittleEndian>(x)?; // x position operand
+    f.write_i16::<LittleEndian>(y)?; // y position operand
+    f.write_u16::<LittleEndian>(length)?; // length operand
+    f.write_u16::<LittleEndian>(component)?; // component operand
+    Ok(())
+}
+
+pub fn link<W: Write>(f: &mut W, from: u16, to: u16, kind: u8) -> Result<()> {
+    f.write_u8(0xA)?; // link instruction
+    f.write_u16::<LittleEndian>(from)?; // source platform operand
+    f.write_u16::<LittleEndian>(to)?; // target platform operand
+    f.write_u8(kind)?; // link kind operand
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn navigation(jump_height: u32, jump_distance: u32) -> Navigation {
+        Navigation {
+            values: vec![1],
+            layers: Vec::new(),
+            jump_height,
+            jump_distance,
+        }
+    }
+
+    fn platform(x: usize, y: usize, length: usize, component: usize) -> Platform {
+        Platform {
+            x,
+            y,
+            length,
+            component,
+        }
+    }
+
+    #[rustfmt::skip]
+    const STEPS: [i64; 30] = [
+        0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 1, 1,
+        0, 0, 0, 0, 0, 0,
+        1, 1, 1, 0, 0, 0,
+    ];
+
+    #[test]
+    fn platforms_are_runs_above_walkable_cells() {
+        let (platforms, _) = navigation(0, 0).graph(&STEPS, 6);
+        assert_eq!(platforms, [platform(4, 1, 2, 0), platform(0, 3, 3, 1)]);
+    }
+
+    #[test]
+    fn links_follow_jump_reach() {
+        // the upper platform is two rows and a column away from the lower one
+        let (platforms, links) = navigation(2, 1).graph(&STEPS, 6);
+        assert_eq!(
+            links,
+            [
+                Link {
+                    from: 0,
+                    to: 1,
+                    kind: FALL
+                },
+                Link {
+                    from: 1,
+                    to: 0,
+                    kind: JUMP
+                }
+            ]
+        );
+        assert!(platforms.iter().all(|platform| platform.component == 0));
+
+        // too high to jump up, but still a fall down
+        let (platforms, links) = navigation(1, 1).graph(&STEPS, 6);
+        assert_eq!(
+            links,
+            [Link {
+                from: 0,
+                to: 1,
+                kind: FALL
+            }]
+        );
+        assert!(platforms.iter().all(|platform| platform.component == 0));
+
+        // too far either way
+        let (platforms, links) = navigation(2, 0).graph(&STEPS, 6);
+        assert!(links.is_empty());
+        assert_eq!(platforms[1].component, 1);
+    }
+
+    #[test]
+    fn bottom_row_cannot_be_stood_in() {
+        let (platforms, links) = navigation(1, 1).graph(&[0, 0, 1, 1], 2);
+        assert_eq!(platforms, [platform(0, 0, 2, 0)]);
+        assert!(links.is_empty());
+        assert!(navigation(1, 1).graph(&[0, 0], 2).0.is_empty());
+    }
+}
@@ -0,0 +1,304 @@
+// traces the outline of IntGrid values into closed polygons, for character controllers and
+// slopes that want contours rather than boxes
+
+use std::{
+    convert::TryFrom,
+    io::{Error, ErrorKind, Result, Write},
+};
+
+use {
+    byteorder::{LittleEndian, WriteBytesExt},
+    hashbrown::HashMap,
+    serde::{Deserialize, Serialize},
+};
+
+/// A corner of the grid, in cells.
+pub type Point = (i32, i32);
+
+/// Which IntGrid values are outlined, as set in the `outline` object of the info table.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Outline {
+    /// IntGrid values to outline, each one on its own, nothing is outlined when empty
+    #[serde(default)]
+    pub values: Vec<i64>,
+
+    /// IntGrid layers to outline, by identifier, every IntGrid layer when empty
+    #[serde(default)]
+    pub layers: Vec<String>,
+
+    /// How far, in cells, a simplified outline may stray from the cells, `0` only drops the
+    /// points in the middle of straight lines
+    #[serde(default)]
+    pub tolerance: f64,
+}
+
+impl Outline {
+    /// Whether this IntGrid layer is outlined.
+    pub fn applies_to(&self, layer: &str) -> bool {
+        !self.values.is_empty()
+            && (self.layers.is_empty() || self.layers.iter().any(|name| name == layer))
+    }
+
+    /// Takes the settings of another table, unless this one already has values.
+    pub fn merge(&mut self, other: Outline) {
+        if self.values.is_empty() {
+            *self = other;
+        }
+    }
+}
+
+/// Traces the outline of every region of `value` in a grid, left to right and top to bottom
+/// like `__intGridCsv`, as closed chains of corners. The first point is not repeated at the
+/// end.
+///
+/// Outer boundaries wind clockwise with y pointing down, holes counter-clockwise, so the
+/// region is always on the right hand side. Regions touching by a corner only are kept apart.
+pub fn chains(cells: &[i64], width: usize, value: i64) -> Vec<Vec<Point>> {
+    if width == 0 {
+        return Vec::new();
+    }
+    let height = (cells.len() + width - 1) / width;
+    let inside = |x: i32, y: i32| {
+        x >= 0 && y >= 0 && (x as usize) < width && {
+            let index = y as usize * width + x as usize;
+            index < cells.len() && cells[index] == value
+        }
+    };
+
+    // one edge per side of a cell facing out of the region, marching squares on the corners
+    let mut edges = Vec::new();
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            if !inside(x, y) {
+                continue;
+            }
+            if !inside(x, y - 1) {
+                edges.push(((x, y), (x + 1, y)));
+            }
+            if !inside(x + 1, y) {
+                edges.push(((x + 1, y), (x + 1, y + 1)));
+            }
+            if !inside(x, y + 1) {
+                edges.push(((x + 1, y + 1), (x, y + 1)));
+            }
+            if !inside(x - 1, y) {
+                edges.push(((x, y + 1), (x, y)));
+            }
+        }
+    }
+    let index = edges
+        .iter()
+        .enumerate()
+        .map(|(index, edge)| (*edge, index))
+        .collect::<HashMap<_, _>>();
+
+    let mut used = vec![false; edges.len()];
+    let mut chains = Vec::new();
+    for start in 0..edges.len() {
+        if used[start] {
+            continue;
+        }
+
+        let mut points = vec![edges[start].0];
+        let mut current = start;
+        loop {
+            used[current] = true;
+            let (from, to) = edges[current];
+            let heading = (to.0 - from.0, to.1 - from.1);
+
+            // turning right first hugs the region, which keeps corner-touching ones apart
+            let right = (-heading.1, heading.0);
+            let left = (heading.1, -heading.0);
+            let next = [right, heading, left]
+                .iter()
+                .find_map(|turn| index.get(&(to, (to.0 + turn.0, to.1 + turn.1))));
+            match next {
+                Some(&next) if next != start => {
+                    points.push(to);
+                    current = next;
+                }
+                _ => break,
+            }
+        }
+        chains.push(corners(&points));
+    }
+    chains
+}
+
+/// Drops the points of a closed chain lying in the middle of a straight line.
+fn corners(points: &[Point]) -> Vec<Point> {
+    let len = points.len();
+    (0..len)
+        .filter(|&index| {
+            let prev = points[(index + len - 1) % len];
+            let point = points[index];
+            let next = points[(index + 1) % len];
+            (point.0 - prev.0) * (next.1 - point.1) != (point.1 - prev.1) * (next.0 - point.0)
+        })
+        .map(|index| points[index])
+        .collect()
+}
+
+/// Simplifies a closed chain with the Ramer-Douglas-Peucker algorithm, dropping points that
+/// stray less than `tolerance` from the simplified outline. Chains that would collapse below
+/// a triangle are left as they are.
+pub fn simplify(points: &[Point], tolerance: f64) -> Vec<Point> {
+    if tolerance <= 0.0 || points.len() <= 3 {
+        return points.to_vec();
+    }
+
+    // a closed chain is split in two open ones, at the point farthest from the first
+    let first = points[0];
+    let (farthest, _) = points
+        .iter()
+        .enumerate()
+        .map(|(index, point)| (index, distance(*point, first, first)))
+        .fold((0, 0.0), |best, candidate| {
+            if candidate.1 > best.1 {
+                candidate
+            } else {
+                best
+            }
+        });
+
+    let mut simplified = Vec::new();
+    douglas_peucker(&points[..=farthest], tolerance, &mut simplified);
+    let mut rest = points[farthest..].to_vec();
+    rest.push(first);
+    douglas_peucker(&rest, tolerance, &mut simplified);
+
+    if simplified.len() < 3 {
+        points.to_vec()
+    } else {
+        simplified
+    }
+}
+
+/// Appends the points of an open chain to keep, all but the last one.
+fn douglas_peucker(points: &[Point], tolerance: f64, out: &mut Vec<Point>) {
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let farthest = (1..points.len() - 1)
+        .map(|index| (index, distance(points[index], first, last)))
+        .fold(None, |best: Option<(usize, f64)>, candidate| match best {
+            Some(best) if best.1 >= candidate.1 => Some(best),
+            _ => Some(candidate),
+        });
+
+    match farthest {
+        Some((index, distance)) if distance > tolerance => {
+            douglas_peucker(&points[..=index], tolerance, out);
+            douglas_peucker(&points[index..], tolerance, out);
+        }
+        _ => out.push(first),
+    }
+}
+
+/// Distance from a point to a segment.
+fn distance(point: Point, start: Point, end: Point) -> f64 {
+    let (px, py) = (f64::from(point.0), f64::from(point.1));
+    let (sx, sy) = (f64::from(start.0), f64::from(start.1));
+    let (dx, dy) = (f64::from(end.0) - sx, f64::from(end.1) - sy);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0.0 {
+        0.0
+    } else {
+        (((px - sx) * dx + (py - sy) * dy) / length).clamp(0.0, 1.0)
+    };
+    ((px - sx - t * dx).powi(2) + (py - sy - t * dy).powi(2)).sqrt()
+}
+
+pub fn polygon<W: Write>(f: &mut W, value: i8, points: &[(i16, i16)]) -> Result<()> {
+    let count = u16::try_from(points.len())
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "outline has too many points"))?;
+    f.write_u8(0x8)?; // outline instruction
+    f.write_i8(value)?; // cell value operand
+    f.write_u16::<LittleEndian>(count)?; // point count
+    for (x, y) in points {
+        f.write_i16::<LittleEndian>(*x)?; // x position operand
+        f.write_i16::<LittleEndian>(*y)?; // y position operand
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_trace_clockwise() {
+        #[rustfmt::skip]
+        let cells = [
+            0, 0, 0,
+            0, 1, 1,
+            0, 1, 1,
+        ];
+        assert_eq!(chains(&cells, 3, 1), [vec![(1, 1), (3, 1), (3, 3), (1, 3)]]);
+    }
+
+    #[test]
+    fn concave_shapes_keep_every_corner() {
+        #[rustfmt::skip]
+        let cells = [
+            1, 0,
+            1, 1,
+        ];
+        assert_eq!(
+            chains(&cells, 2, 1),
+            [vec![(0, 0), (1, 0), (1, 1), (2, 1), (2, 2), (0, 2)]]
+        );
+    }
+
+    #[test]
+    fn holes_trace_counter_clockwise() {
+        #[rustfmt::skip]
+        let cells = [
+            1, 1, 1,
+            1, 0, 1,
+            1, 1, 1,
+        ];
+        let chains = chains(&cells, 3, 1);
+        assert_eq!(chains.len(), 2);
+        assert_eq!(chains[0], [(0, 0), (3, 0), (3, 3), (0, 3)]);
+        assert_eq!(chains[1], [(2, 1), (1, 1), (1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn corner_touching_regions_stay_apart() {
+        #[rustfmt::skip]
+        let cells = [
+            1, 0,
+            0, 1,
+        ];
+        assert_eq!(
+            chains(&cells, 2, 1),
+            [
+                vec![(0, 0), (1, 0), (1, 1), (0, 1)],
+                vec![(1, 1), (2, 1), (2, 2), (1, 2)]
+            ]
+        );
+    }
+
+    #[test]
+    fn simplify_drops_small_steps() {
+        // a slope of single cell steps, within a cell of the straight line
+        let staircase = [
+            (0, 0),
+            (1, 0),
+            (1, 1),
+            (2, 1),
+            (2, 2),
+            (3, 2),
+            (3, 3),
+            (0, 3),
+        ];
+        assert_eq!(simplify(&staircase, 0.0), staircase);
+        assert_eq!(simplify(&staircase, 1.0), [(0, 0), (3, 3), (0, 3)]);
+    }
+
+    #[test]
+    fn simplify_keeps_at_least_a_triangle() {
+        let square = [(0, 0), (1, 0), (1, 1), (0, 1)];
+        assert_eq!(simplify(&square, 10.0), square);
+    }
+}
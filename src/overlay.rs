@@ -0,0 +1,238 @@
+// writes an SVG of each level for code review: labeled entity boxes, IntGrid cells and one group
+// per layer, toggled by id. One element per line, in project order, so moving a spawn shows
+// up as a one-line diff
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    batch, compat,
+    runtime::{self, Level, Project},
+};
+
+/// File extension of overlays.
+pub const EXTENSION: &str = "svg";
+
+/// Writes the overlay of a single level, whose layer instances must already be loaded. Layers
+/// are drawn bottom to top, like LDtk does.
+pub fn write<W: Write>(f: &mut W, project: &Project, level: &Level) -> io::Result<()> {
+    let layers = level.layer_instances.as_deref().unwrap_or_default();
+    let (width, height) = (level.px_wid, level.px_hei);
+
+    writeln!(
+        f,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = width,
+        h = height
+    )?;
+    writeln!(f, "<title>{}</title>", escape(&level.identifier))?;
+    writeln!(
+        f,
+        "<style>text {{ font: 6px sans-serif; fill: #FFFFFF; paint-order: stroke; stroke: #000000; stroke-width: 1px; }}</style>"
+    )?;
+    writeln!(
+        f,
+        r#"<rect id="background" width="{}" height="{}" fill="{}"/>"#,
+        width,
+        height,
+        escape(&level.bg_color)
+    )?;
+
+    for layer in layers.iter().rev() {
+        writeln!(
+            f,
+            r#"<g id="layer-{}" data-type="{}">"#,
+            escape(&layer.identifier),
+            escape(&layer.layer_instance_type)
+        )?;
+
+        // IntGrid cells
+        let def = project
+            .defs
+            .layers
+            .iter()
+            .find(|def| def.uid == layer.layer_def_uid);
+        let grid = layer.grid_size;
+        let columns = layer.c_wid.max(1) as usize;
+        for (index, value) in layer.int_grid_csv.iter().enumerate() {
+            if *value == 0 {
+                continue;
+            }
+            let color = def
+                .and_then(|def| def.int_grid_values.iter().find(|v| v.value == *value))
+                .map_or("#FF00FF", |v| v.color.as_str());
+            writeln!(
+                f,
+                r#"<rect x="{}" y="{}" width="{g}" height="{g}" fill="{}" data-value="{}"/>"#,
+                (index % columns) as i64 * grid,
+                (index / columns) as i64 * grid,
+                escape(color),
+                value,
+                g = grid
+            )?;
+        }
+
+        // entities, positioned by their pivot
+        for entity in layer.entity_instances.iter() {
+            let (x, y) = match entity.px.as_slice() {
+                [x, y] => (*x, *y),
+                _ => continue,
+            };
+            let (pivot_x, pivot_y) = match entity.pivot.as_slice() {
+                [pivot_x, pivot_y] => (*pivot_x, *pivot_y),
+                _ => (0.0, 0.0),
+            };
+            let left = x as f64 - pivot_x * entity.width as f64;
+            let top = y as f64 - pivot_y * entity.height as f64;
+            let color = project
+                .defs
+                .entities
+                .iter()
+                .find(|def| def.uid == entity.def_uid)
+                .map_or("#FF00FF", |def| def.color.as_str());
+            let color = escape(color);
+
+            writeln!(
+                f,
+                r#"<g id="entity-{}" class="entity" data-identifier="{}">"#,
+                escape(&entity.iid),
+                escape(&entity.identifier)
+            )?;
+            writeln!(
+                f,
+                "<title>{} at ({}, {}), pivot ({}, {}), {}x{}</title>",
+                escape(&entity.identifier),
+                x,
+                y,
+                pivot_x,
+                pivot_y,
+                entity.width,
+                entity.height
+            )?;
+            writeln!(
+                f,
+                r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{c}" fill-opacity="0.25" stroke="{c}"/>"#,
+                left,
+                top,
+                entity.width,
+                entity.height,
+                c = color
+            )?;
+            writeln!(
+                f,
+                r#"<circle class="pivot" cx="{}" cy="{}" r="1.5" fill="{}"/>"#,
+                x, y, color
+            )?;
+            writeln!(
+                f,
+                r#"<text x="{}" y="{}">{} ({}, {}) {}x{}</text>"#,
+                left,
+                top - 1.0,
+                escape(&entity.identifier),
+                x,
+                y,
+                entity.width,
+                entity.height
+            )?;
+            writeln!(f, "</g>")?;
+        }
+
+        writeln!(f, "</g>")?;
+    }
+    writeln!(f, "</svg>")
+}
+
+/// Writes the overlay of every level of a project into `output_dir`, named after the level.
+/// Returns the path of each overlay written.
+pub fn run(project_path: &Path, output_dir: &Path) -> Result<Vec<PathBuf>> {
+    let raw = fs::read_to_string(project_path)
+        .with_context(|| format!("failed to read {}", project_path.display()))?;
+    let project: Project = compat::parse_project(&raw)?;
+
+    fs::create_dir_all(output_dir)
+        .with_context(|| format!("failed to create {}", output_dir.display()))?;
+
+    let mut paths = Vec::new();
+    let levels = project
+        .levels
+        .iter()
+        .chain(project.worlds.iter().flat_map(|world| world.levels.iter()));
+    for level in levels {
+        let map = runtime::load_level(project_path, &project, level)?;
+        let path = output_dir.join(format!("{}.{}", level.identifier, EXTENSION));
+        batch::write_atomic(&path, |mut dst| {
+            write(&mut dst, &project, &map)
+                .with_context(|| format!("failed to write {}", path.display()))
+        })?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Escapes text and attribute values.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::runtime::fixtures;
+
+    #[test]
+    fn elements_are_written_one_per_line() {
+        let mut door = fixtures::entity("Door", (24, 32), &[], json!([]));
+        door["__pivot"] = json!([0.5, 1.0]);
+        let project = fixtures::project(
+            fixtures::defs(),
+            vec![
+                fixtures::entities(vec![door]),
+                fixtures::int_grid(2, &[0, 2]),
+            ],
+        );
+
+        let mut out = Vec::new();
+        write(&mut out, &project, &project.levels[0]).unwrap();
+        let svg = String::from_utf8(out).unwrap();
+        let lines = svg.lines().collect::<Vec<_>>();
+
+        // layers are drawn bottom to top
+        let grid = lines
+            .iter()
+            .position(|line| *line == r#"<g id="layer-Grid" data-type="IntGrid">"#)
+            .unwrap();
+        let entities = lines
+            .iter()
+            .position(|line| *line == r#"<g id="layer-Entities" data-type="Entities">"#)
+            .unwrap();
+        assert!(grid < entities);
+        assert_eq!(
+            lines[grid + 1],
+            r##"<rect x="16" y="0" width="16" height="16" fill="#0000FF" data-value="2"/>"##
+        );
+        assert_eq!(lines[grid + 2], "</g>");
+
+        assert_eq!(
+            &lines[entities + 1..entities + 4],
+            [
+                r#"<g id="entity-Door-24-32" class="entity" data-identifier="Door">"#,
+                "<title>Door at (24, 32), pivot (0.5, 1), 16x16</title>",
+                r##"<rect x="16" y="16" width="16" height="16" fill="#FF00FF" fill-opacity="0.25" stroke="#FF00FF"/>"##,
+            ]
+        );
+        assert_eq!(
+            lines[entities + 4],
+            r##"<circle class="pivot" cx="24" cy="32" r="1.5" fill="#FF00FF"/>"##
+        );
+    }
+}
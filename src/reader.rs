@@ -0,0 +1,948 @@
+// decodes the command stream of an LVL file, the inverse of the writer functions in
+// `global.rs`, `layer.rs`, `grid.rs`, `entity.rs` and `tile.rs`
+
+use std::{
+    fmt,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+};
+
+use {
+    byteorder::{LittleEndian, ReadBytesExt},
+    flate2::read::ZlibDecoder,
+};
+
+use crate::{
+    checksum, entity,
+    error::ReadError,
+    global, navigation,
+    section::{self, Entry},
+};
+
+/// A single decoded instruction, operands named after the writer function parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    GlobalWidth(u64),
+    GlobalHeight(u64),
+
+    /// Name of the level, as an index into the string table
+    GlobalName(u32),
+
+    LayerWidth(u32),
+    LayerHeight(u32),
+    LayerType(u8),
+
+    /// Name of the layer, as an index into the string table
+    LayerName(u32),
+
+    GridCell {
+        x: i16,
+        y: i16,
+        value: i8,
+    },
+
+    Entity {
+        entity: i32,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        rotation: i16,
+        flipped_x: bool,
+        flipped_y: bool,
+
+        /// Offset of the position from the top left corner of the entity
+        origin_x: i32,
+        origin_y: i32,
+    },
+
+    /// Tags of the entity created just before
+    EntityTags(Vec<i32>),
+
+    /// Tile the entity created just before is displayed with, source rectangle in pixels
+    EntitySprite {
+        tileset: i32,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+        mode: u8,
+
+        /// Nine-slice borders, top, right, bottom then left
+        borders: [u16; 4],
+    },
+
+    /// Strings referenced by index from other commands
+    StringTable(Vec<String>),
+
+    /// Closed outline of a region of cells sharing a value, corners in cells
+    Outline {
+        value: i8,
+        points: Vec<(i16, i16)>,
+    },
+
+    /// Horizontal run of cells an agent can stand in, in cells
+    Platform {
+        x: i16,
+        y: i16,
+        length: u16,
+        component: u16,
+    },
+
+    /// Way from one platform to another, by index in the navigation section
+    Link {
+        from: u16,
+        to: u16,
+        kind: u8,
+    },
+
+    /// Solid cells merged into a rectangle, in cells
+    CollisionRect {
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+        value: i8,
+    },
+
+    Tile {
+        tileset: i32,
+        x: u32,
+        y: u32,
+        tile_x: u16,
+        tile_y: u16,
+    },
+
+    /// Sprite placed by its center, source rectangle in pixels
+    Decal {
+        tileset: i32,
+        tile_x: u16,
+        tile_y: u16,
+        tile_width: u16,
+        tile_height: u16,
+        x: i32,
+        y: i32,
+        scale_x: f32,
+        scale_y: f32,
+        rotation: i16,
+
+        /// `0xRRGGBBAA`
+        tint: u32,
+    },
+
+    /// CRC-32 of every byte before this command, always the last one of the file
+    Checksum(u32),
+}
+
+/// Reads the commands of an LVL file one at a time, Format 1 files can also be read a single
+/// section at a time.
+pub struct Reader<R> {
+    inner: Source<R>,
+
+    /// Format version of the file
+    format: u8,
+
+    /// Header flags of a Format 1 file, always 0 for Format 0 files
+    flags: u32,
+
+    /// Section directory, empty for Format 0 files
+    sections: Vec<Entry>,
+
+    /// Last string table read
+    strings: Vec<String>,
+
+    /// Bytes consumed so far, header included
+    offset: u64,
+
+    /// Set once an error was returned, the stream cannot be resynchronized after one
+    failed: bool,
+}
+
+/// The bytes a reader decodes, compressed files being inflated in memory up front.
+enum Source<R> {
+    Plain(R),
+
+    /// Content of a compressed file, starting right after its flags
+    Inflated(Cursor<Vec<u8>>),
+}
+
+impl<R: Read> Read for Source<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Source::Plain(inner) => inner.read(buf),
+            Source::Inflated(data) => data.read(buf),
+        }
+    }
+}
+
+impl<R: Seek> Seek for Source<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Source::Plain(inner) => inner.seek(pos),
+
+            // offsets are those of the uncompressed file, which includes the preamble
+            Source::Inflated(data) => {
+                let pos = match pos {
+                    SeekFrom::Start(offset) => SeekFrom::Start(
+                        offset.checked_sub(section::PREAMBLE_SIZE).ok_or_else(|| {
+                            io::Error::new(io::ErrorKind::InvalidInput, "seek into the preamble")
+                        })?,
+                    ),
+                    pos => pos,
+                };
+                data.seek(pos).map(|offset| offset + section::PREAMBLE_SIZE)
+            }
+        }
+    }
+}
+
+impl<R: Read> Reader<R> {
+    /// Checks the header, reads the section directory of Format 1 files and positions the
+    /// reader on the first command.
+    pub fn new(mut inner: R) -> Result<Self, ReadError> {
+        let mut header = [0; global::HEADER.len()];
+        inner
+            .read_exact(&mut header)
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::UnexpectedEof => ReadError::BadHeader,
+                _ => ReadError::Io(err),
+            })?;
+
+        if header == global::HEADER {
+            return Ok(Reader {
+                inner: Source::Plain(inner),
+                format: 0,
+                flags: 0,
+                sections: Vec::new(),
+                strings: Vec::new(),
+                offset: global::HEADER.len() as u64,
+                failed: false,
+            });
+        }
+        if header != global::HEADER_1 {
+            return Err(ReadError::BadHeader);
+        }
+
+        let offset = global::HEADER_1.len() as u64;
+        let truncated = |err: io::Error| match err.kind() {
+            io::ErrorKind::UnexpectedEof => ReadError::Truncated { offset },
+            _ => ReadError::Io(err),
+        };
+        let flags = inner.read_u32::<LittleEndian>().map_err(truncated)?;
+        if flags & !section::FLAGS != 0 {
+            return Err(ReadError::UnsupportedFlags(flags));
+        }
+        let mut inner = if flags & section::FLAG_DEFLATE != 0 {
+            Source::Inflated(Cursor::new(inflate(inner, flags)?))
+        } else {
+            Source::Plain(inner)
+        };
+        let count = inner.read_u32::<LittleEndian>().map_err(truncated)?;
+        let sections = (0..count)
+            .map(|_| {
+                Ok(Entry {
+                    kind: inner.read_u8()?,
+                    layer_type: inner.read_u8()?,
+                    offset: inner.read_u32::<LittleEndian>()?,
+                    length: inner.read_u32::<LittleEndian>()?,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()
+            .map_err(truncated)?;
+
+        Ok(Reader {
+            inner,
+            format: 1,
+            flags,
+            sections,
+            strings: Vec::new(),
+            offset: section::HEADER_SIZE + section::ENTRY_SIZE * u64::from(count),
+            failed: false,
+        })
+    }
+
+    /// Format version of the file.
+    pub fn format(&self) -> u8 {
+        self.format
+    }
+
+    /// Header flags of a Format 1 file, 0 for Format 0 files.
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// Section directory of a Format 1 file, empty for Format 0 files.
+    pub fn sections(&self) -> &[Entry] {
+        &self.sections
+    }
+
+    /// String at `index` of the last string table read, which comes before any command
+    /// referencing it.
+    pub fn string(&self, index: u32) -> Option<&str> {
+        self.strings.get(index as usize).map(String::as_str)
+    }
+
+    /// Offset of the next command from the start of the file.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Decodes the next command, `None` once the end of the file is reached.
+    pub fn next_command(&mut self) -> Result<Option<Command>, ReadError> {
+        if self.failed {
+            return Ok(None);
+        }
+
+        let result = self.decode();
+        match &result {
+            Err(_) => self.failed = true,
+            Ok(Some((command, len))) => {
+                self.offset += len;
+                if let Command::StringTable(strings) = command {
+                    self.strings = strings.clone();
+                }
+            }
+            Ok(None) => {}
+        }
+        result.map(|command| command.map(|(command, _)| command))
+    }
+
+    /// Decodes a command along with its length in bytes.
+    fn decode(&mut self) -> Result<Option<(Command, u64)>, ReadError> {
+        let offset = self.offset;
+        let truncated = |err: io::Error| match err.kind() {
+            io::ErrorKind::UnexpectedEof => ReadError::Truncated { offset },
+            _ => ReadError::Io(err),
+        };
+
+        let mut code = [0];
+        loop {
+            match self.inner.read(&mut code) {
+                Ok(0) => return Ok(None),
+                Ok(_) => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(ReadError::Io(err)),
+            }
+        }
+
+        let f = &mut self.inner;
+        let command = match code[0] {
+            0x0 => match f.read_u8().map_err(truncated)? {
+                0x0 => (
+                    Command::GlobalWidth(f.read_u64::<LittleEndian>().map_err(truncated)?),
+                    10,
+                ),
+                0x1 => (
+                    Command::GlobalHeight(f.read_u64::<LittleEndian>().map_err(truncated)?),
+                    10,
+                ),
+                0x4 => (
+                    Command::GlobalName(f.read_u32::<LittleEndian>().map_err(truncated)?),
+                    6,
+                ),
+                flag => {
+                    return Err(ReadError::UnknownFlag {
+                        command: 0x0,
+                        flag,
+                        offset,
+                    })
+                }
+            },
+
+            0x1 => match f.read_u8().map_err(truncated)? {
+                0x0 => (
+                    Command::LayerWidth(f.read_u32::<LittleEndian>().map_err(truncated)?),
+                    6,
+                ),
+                0x1 => (
+                    Command::LayerHeight(f.read_u32::<LittleEndian>().map_err(truncated)?),
+                    6,
+                ),
+                0x4 => (Command::LayerType(f.read_u8().map_err(truncated)?), 3),
+                0x5 => (
+                    Command::LayerName(f.read_u32::<LittleEndian>().map_err(truncated)?),
+                    6,
+                ),
+                flag => {
+                    return Err(ReadError::UnknownFlag {
+                        command: 0x1,
+                        flag,
+                        offset,
+                    })
+                }
+            },
+
+            0x2 => (
+                Command::GridCell {
+                    x: f.read_i16::<LittleEndian>().map_err(truncated)?,
+                    y: f.read_i16::<LittleEndian>().map_err(truncated)?,
+                    value: f.read_i8().map_err(truncated)?,
+                },
+                6,
+            ),
+
+            0x3 => (
+                Command::Entity {
+                    entity: f.read_i32::<LittleEndian>().map_err(truncated)?,
+                    x: f.read_i32::<LittleEndian>().map_err(truncated)?,
+                    y: f.read_i32::<LittleEndian>().map_err(truncated)?,
+                    width: f.read_u32::<LittleEndian>().map_err(truncated)?,
+                    height: f.read_u32::<LittleEndian>().map_err(truncated)?,
+                    rotation: f.read_i16::<LittleEndian>().map_err(truncated)?,
+                    flipped_x: f.read_u8().map_err(truncated)? != 0,
+                    flipped_y: f.read_u8().map_err(truncated)? != 0,
+                    origin_x: f.read_i32::<LittleEndian>().map_err(truncated)?,
+                    origin_y: f.read_i32::<LittleEndian>().map_err(truncated)?,
+                },
+                33,
+            ),
+
+            0x5 => {
+                let count = f.read_u16::<LittleEndian>().map_err(truncated)?;
+                let tags = (0..count)
+                    .map(|_| f.read_i32::<LittleEndian>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(truncated)?;
+                (Command::EntityTags(tags), 3 + 4 * u64::from(count))
+            }
+
+            0xB => {
+                let tileset = f.read_i32::<LittleEndian>().map_err(truncated)?;
+                let mut rect = [0; 4];
+                f.read_u16_into::<LittleEndian>(&mut rect)
+                    .map_err(truncated)?;
+                let mode = f.read_u8().map_err(truncated)?;
+                let mut borders = [0; 4];
+                f.read_u16_into::<LittleEndian>(&mut borders)
+                    .map_err(truncated)?;
+                let [x, y, width, height] = rect;
+                (
+                    Command::EntitySprite {
+                        tileset,
+                        x,
+                        y,
+                        width,
+                        height,
+                        mode,
+                        borders,
+                    },
+                    22,
+                )
+            }
+
+            0x6 => {
+                let (strings, len) = read_strings(f, offset)?;
+                (Command::StringTable(strings), 1 + len)
+            }
+
+            0x8 => {
+                let value = f.read_i8().map_err(truncated)?;
+                let count = f.read_u16::<LittleEndian>().map_err(truncated)?;
+                let points = (0..count)
+                    .map(|_| Ok((f.read_i16::<LittleEndian>()?, f.read_i16::<LittleEndian>()?)))
+                    .collect::<io::Result<Vec<_>>>()
+                    .map_err(truncated)?;
+                (Command::Outline { value, points }, 4 + 4 * u64::from(count))
+            }
+
+            0x9 => (
+                Command::Platform {
+                    x: f.read_i16::<LittleEndian>().map_err(truncated)?,
+                    y: f.read_i16::<LittleEndian>().map_err(truncated)?,
+                    length: f.read_u16::<LittleEndian>().map_err(truncated)?,
+                    component: f.read_u16::<LittleEndian>().map_err(truncated)?,
+                },
+                9,
+            ),
+
+            0xA => (
+                Command::Link {
+                    from: f.read_u16::<LittleEndian>().map_err(truncated)?,
+                    to: f.read_u16::<LittleEndian>().map_err(truncated)?,
+                    kind: f.read_u8().map_err(truncated)?,
+                },
+                6,
+            ),
+
+            0x7 => (
+                Command::CollisionRect {
+                    x: f.read_i16::<LittleEndian>().map_err(truncated)?,
+                    y: f.read_i16::<LittleEndian>().map_err(truncated)?,
+                    width: f.read_u16::<LittleEndian>().map_err(truncated)?,
+                    height: f.read_u16::<LittleEndian>().map_err(truncated)?,
+                    value: f.read_i8().map_err(truncated)?,
+                },
+                10,
+            ),
+
+            0x4 => (
+                Command::Tile {
+                    tileset: f.read_i32::<LittleEndian>().map_err(truncated)?,
+                    x: f.read_u32::<LittleEndian>().map_err(truncated)?,
+                    y: f.read_u32::<LittleEndian>().map_err(truncated)?,
+                    tile_x: f.read_u16::<LittleEndian>().map_err(truncated)?,
+                    tile_y: f.read_u16::<LittleEndian>().map_err(truncated)?,
+                },
+                17,
+            ),
+
+            0xC => (
+                Command::Decal {
+                    tileset: f.read_i32::<LittleEndian>().map_err(truncated)?,
+                    tile_x: f.read_u16::<LittleEndian>().map_err(truncated)?,
+                    tile_y: f.read_u16::<LittleEndian>().map_err(truncated)?,
+                    tile_width: f.read_u16::<LittleEndian>().map_err(truncated)?,
+                    tile_height: f.read_u16::<LittleEndian>().map_err(truncated)?,
+                    x: f.read_i32::<LittleEndian>().map_err(truncated)?,
+                    y: f.read_i32::<LittleEndian>().map_err(truncated)?,
+                    scale_x: f.read_f32::<LittleEndian>().map_err(truncated)?,
+                    scale_y: f.read_f32::<LittleEndian>().map_err(truncated)?,
+                    rotation: f.read_i16::<LittleEndian>().map_err(truncated)?,
+                    tint: f.read_u32::<LittleEndian>().map_err(truncated)?,
+                },
+                35,
+            ),
+
+            0xFF => (
+                Command::Checksum(f.read_u32::<LittleEndian>().map_err(truncated)?),
+                5,
+            ),
+
+            code => return Err(ReadError::UnknownCommand { code, offset }),
+        };
+
+        Ok(Some(command))
+    }
+}
+
+impl Reader<Cursor<Vec<u8>>> {
+    /// Reads a whole LVL file and checks its checksum before decoding anything, so a corrupted
+    /// or partially downloaded file is refused up front. Files without a checksum are refused
+    /// too, as a clean cut between two commands could not be told apart from a complete file.
+    pub fn verified<R: Read>(mut src: R) -> Result<Self, ReadError> {
+        let mut data = Vec::new();
+        src.read_to_end(&mut data).map_err(ReadError::Io)?;
+        verify(&data)?.ok_or(ReadError::MissingChecksum)?;
+        Reader::new(Cursor::new(data))
+    }
+}
+
+impl<R: Read + Seek> Reader<R> {
+    /// Decodes a single section of a Format 1 file, seeking straight to it. Reading command by
+    /// command afterwards resumes right after that section.
+    pub fn read_section(&mut self, index: usize) -> Result<Vec<Command>, ReadError> {
+        let entry = *self
+            .sections
+            .get(index)
+            .ok_or(ReadError::MissingSection(index))?;
+        let end = u64::from(entry.offset) + u64::from(entry.length);
+
+        self.inner
+            .seek(SeekFrom::Start(u64::from(entry.offset)))
+            .map_err(ReadError::Io)?;
+        self.offset = u64::from(entry.offset);
+        self.failed = false;
+
+        let mut commands = Vec::new();
+        while self.offset < end {
+            let offset = self.offset;
+            match self.next_command()? {
+                Some(command) if self.offset <= end => commands.push(command),
+                Some(_) => {
+                    self.failed = true;
+                    return Err(ReadError::SectionOverrun { index, offset });
+                }
+                None => return Err(ReadError::Truncated { offset }),
+            }
+        }
+        Ok(commands)
+    }
+}
+
+/// Inflates the content of a compressed Format 1 file, up to the end of its last section (and
+/// checksum) as recorded in its section directory, so a corrupted or hostile stream cannot
+/// inflate to more than the file declares.
+fn inflate<R: Read>(inner: R, flags: u32) -> Result<Vec<u8>, ReadError> {
+    let mut decoder = ZlibDecoder::new(inner);
+    let mut data = Vec::new();
+    let mut read = |data: &mut Vec<u8>, len: u64| {
+        (&mut decoder)
+            .take(len)
+            .read_to_end(data)
+            .map_err(ReadError::Decompress)
+    };
+
+    // the directory comes first, a truncated one is reported once it is decoded
+    read(&mut data, 4)?;
+    let count = match data.as_slice() {
+        [a, b, c, d] => u32::from_le_bytes([*a, *b, *c, *d]),
+        _ => return Ok(data),
+    };
+    read(&mut data, section::ENTRY_SIZE * u64::from(count))?;
+
+    let directory_end = section::PREAMBLE_SIZE + data.len() as u64;
+    let sections_end = data[4..]
+        .chunks_exact(section::ENTRY_SIZE as usize)
+        .map(|entry| {
+            let offset = u32::from_le_bytes([entry[2], entry[3], entry[4], entry[5]]);
+            let length = u32::from_le_bytes([entry[6], entry[7], entry[8], entry[9]]);
+            u64::from(offset) + u64::from(length)
+        })
+        .fold(directory_end, u64::max);
+    let end = match flags & section::FLAG_CHECKSUM {
+        0 => sections_end,
+        _ => sections_end + global::CHECKSUM_SIZE,
+    };
+    read(&mut data, end - directory_end)?;
+
+    if decoder.read(&mut [0]).map_err(ReadError::Decompress)? != 0 {
+        return Err(ReadError::Decompress(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "content inflates past the end of its last section",
+        )));
+    }
+    Ok(data)
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Command, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_command().transpose()
+    }
+}
+
+/// Reads every command of an LVL file.
+pub fn read_all<R: Read>(src: R) -> Result<Vec<Command>, ReadError> {
+    Reader::new(src)?.collect()
+}
+
+/// Checks the checksum of a whole LVL file, returning it if the file has one.
+///
+/// Format 1 files announce it in their header flags, so it is checked before any command is
+/// decoded. Format 0 files have no flags and are walked command by command until it is found.
+pub fn verify(data: &[u8]) -> Result<Option<u32>, ReadError> {
+    let mut reader = Reader::new(data)?;
+
+    // the checksum covers the uncompressed file
+    let inflated;
+    let data = match &reader.inner {
+        Source::Inflated(content) => {
+            let preamble = &data[..section::PREAMBLE_SIZE as usize];
+            inflated = [preamble, content.get_ref()].concat();
+            &inflated
+        }
+        Source::Plain(_) => data,
+    };
+
+    let offset = if reader.format() == 0 {
+        let mut checksum = None;
+        while let Some(command) = reader.next_command()? {
+            if let Command::Checksum(_) = command {
+                checksum = Some(reader.offset() - global::CHECKSUM_SIZE);
+                break;
+            }
+        }
+        match checksum {
+            Some(offset) => offset,
+            None => return Ok(None),
+        }
+    } else if reader.flags() & section::FLAG_CHECKSUM != 0 {
+        let offset = data
+            .len()
+            .checked_sub(global::CHECKSUM_SIZE as usize)
+            .ok_or(ReadError::MissingChecksum)? as u64;
+        if offset < reader.offset() || data[offset as usize] != 0xFF {
+            return Err(ReadError::MissingChecksum);
+        }
+        offset
+    } else {
+        return Ok(None);
+    };
+
+    // the checksum command ends the file, anything after it was not covered
+    let trailer = &data[offset as usize..];
+    if trailer.len() != 5 {
+        return Err(ReadError::TrailingData { offset: offset + 5 });
+    }
+    let expected = u32::from_le_bytes([trailer[1], trailer[2], trailer[3], trailer[4]]);
+    let found = checksum::crc32(&data[..offset as usize]);
+    if expected != found {
+        return Err(ReadError::ChecksumMismatch { expected, found });
+    }
+    Ok(Some(found))
+}
+
+/// Reads the operands of a string table command starting at `offset`, returning the strings
+/// and how many bytes they took.
+pub(crate) fn read_strings<R: Read>(
+    f: &mut R,
+    offset: u64,
+) -> Result<(Vec<String>, u64), ReadError> {
+    let truncated = |err: io::Error| match err.kind() {
+        io::ErrorKind::UnexpectedEof => ReadError::Truncated { offset },
+        _ => ReadError::Io(err),
+    };
+
+    let count = f.read_u32::<LittleEndian>().map_err(truncated)?;
+    let mut strings = Vec::new();
+    let mut len = 4;
+    for _ in 0..count {
+        let size = f.read_u32::<LittleEndian>().map_err(truncated)?;
+        // read through `take` so a corrupted length cannot allocate gigabytes
+        let mut bytes = Vec::new();
+        f.by_ref()
+            .take(u64::from(size))
+            .read_to_end(&mut bytes)
+            .map_err(ReadError::Io)?;
+        if bytes.len() != size as usize {
+            return Err(ReadError::Truncated { offset });
+        }
+        let string = String::from_utf8(bytes).map_err(|_| ReadError::InvalidString { offset })?;
+        strings.push(string);
+        len += 4 + u64::from(size);
+    }
+    Ok((strings, len))
+}
+
+/// Human readable name of a layer type operand.
+pub fn layer_type_name(typ: u8) -> &'static str {
+    match typ {
+        0 => "tile",
+        1 => "grid",
+        2 => "decal",
+        3 => "entity",
+        _ => "unknown",
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::GlobalWidth(w) => write!(f, "global width {}", w),
+            Command::GlobalHeight(h) => write!(f, "global height {}", h),
+            Command::GlobalName(name) => write!(f, "global name #{}", name),
+            Command::LayerWidth(w) => write!(f, "layer width {}", w),
+            Command::LayerHeight(h) => write!(f, "layer height {}", h),
+            Command::LayerName(name) => write!(f, "layer name #{}", name),
+            Command::LayerType(typ) => {
+                write!(f, "layer type {} ({})", typ, layer_type_name(*typ))
+            }
+            Command::GridCell { x, y, value } => {
+                write!(f, "grid cell ({}, {}) = {}", x, y, value)
+            }
+            Command::Entity {
+                entity,
+                x,
+                y,
+                width,
+                height,
+                rotation,
+                flipped_x,
+                flipped_y,
+                origin_x,
+                origin_y,
+            } => write!(
+                f,
+                "entity {} at ({}, {}) size {}x{} rotation {} flipped x {} flipped y {} origin ({}, {})",
+                entity, x, y, width, height, rotation, flipped_x, flipped_y, origin_x, origin_y
+            ),
+            Command::EntityTags(tags) => {
+                write!(f, "entity tags")?;
+                for tag in tags {
+                    write!(f, " {}", tag)?;
+                }
+                Ok(())
+            }
+            Command::EntitySprite {
+                tileset,
+                x,
+                y,
+                width,
+                height,
+                mode,
+                borders,
+            } => {
+                write!(
+                    f,
+                    "entity sprite from tileset {} source ({}, {}) size {}x{} {}",
+                    tileset,
+                    x,
+                    y,
+                    width,
+                    height,
+                    entity::render_mode_name(*mode)
+                )?;
+                if *mode == entity::NINE_SLICE {
+                    write!(
+                        f,
+                        " borders {} {} {} {}",
+                        borders[0], borders[1], borders[2], borders[3]
+                    )?;
+                }
+                Ok(())
+            }
+            Command::StringTable(strings) => {
+                write!(f, "string table")?;
+                for (index, string) in strings.iter().enumerate() {
+                    write!(f, " #{} {:?}", index, string)?;
+                }
+                Ok(())
+            }
+            Command::Tile {
+                tileset,
+                x,
+                y,
+                tile_x,
+                tile_y,
+            } => write!(
+                f,
+                "tile from tileset {} at ({}, {}) source ({}, {})",
+                tileset, x, y, tile_x, tile_y
+            ),
+            Command::Decal {
+                tileset,
+                tile_x,
+                tile_y,
+                tile_width,
+                tile_height,
+                x,
+                y,
+                scale_x,
+                scale_y,
+                rotation,
+                tint,
+            } => write!(
+                f,
+                "decal from tileset {} source ({}, {}) size {}x{} at ({}, {}) scale {}x{} rotation {} tint {:#010x}",
+                tileset, tile_x, tile_y, tile_width, tile_height, x, y, scale_x, scale_y, rotation, tint
+            ),
+            Command::Outline { value, points } => {
+                write!(f, "outline = {}, {} points", value, points.len())?;
+                for (x, y) in points {
+                    write!(f, " ({}, {})", x, y)?;
+                }
+                Ok(())
+            }
+            Command::CollisionRect {
+                x,
+                y,
+                width,
+                height,
+                value,
+            } => write!(
+                f,
+                "collision rectangle ({}, {}) size {}x{} = {}",
+                x, y, width, height, value
+            ),
+            Command::Platform {
+                x,
+                y,
+                length,
+                component,
+            } => write!(
+                f,
+                "platform ({}, {}) length {} component {}",
+                x, y, length, component
+            ),
+            Command::Link { from, to, kind } => write!(
+                f,
+                "{} link {} -> {}",
+                match *kind {
+                    navigation::JUMP => "jump",
+                    navigation::FALL => "fall",
+                    _ => "unknown",
+                },
+                from,
+                to
+            ),
+            Command::Checksum(crc) => write!(f, "checksum {:#010x}", crc),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::ZlibEncoder, Compression};
+
+    use super::*;
+    use crate::{grid, layer, section::Section};
+
+    /// A Format 1 file with an empty metadata section and a grid layer of one cell.
+    fn sections() -> Vec<Section> {
+        let mut data = Vec::new();
+        layer::set_type(&mut data, 1).unwrap();
+        grid::cell_set(&mut data, 3, 4, 2).unwrap();
+        vec![Section::metadata(Vec::new()), Section::layer(1, data)]
+    }
+
+    #[test]
+    fn compressed_files_inflate_to_their_directory() {
+        let mut file = Vec::new();
+        section::write(&mut file, 1, section::FLAG_DEFLATE, &sections()).unwrap();
+        let commands = read_all(Cursor::new(file)).unwrap();
+        assert_eq!(
+            commands,
+            [
+                Command::LayerType(1),
+                Command::GridCell {
+                    x: 3,
+                    y: 4,
+                    value: 2
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn checksums_cover_the_inflated_content() {
+        let mut file = Vec::new();
+        section::write(&mut file, 1, section::FLAGS, &sections()).unwrap();
+        let crc = verify(&file).unwrap().unwrap();
+        assert!(Reader::verified(Cursor::new(file)).is_ok());
+
+        let mut plain = Vec::new();
+        section::write(&mut plain, 1, section::FLAG_CHECKSUM, &sections()).unwrap();
+        plain[section::PREAMBLE_SIZE as usize - 4] = section::FLAGS as u8;
+        assert_eq!(
+            checksum::crc32(&plain[..plain.len() - global::CHECKSUM_SIZE as usize]),
+            crc
+        );
+    }
+
+    #[test]
+    fn corrupted_files_fail_their_checksum() {
+        let mut file = Vec::new();
+        section::write(&mut file, 1, section::FLAG_CHECKSUM, &sections()).unwrap();
+        let last = file.len() - global::CHECKSUM_SIZE as usize - 1;
+        file[last] ^= 1;
+        assert!(matches!(
+            verify(&file),
+            Err(ReadError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn compressed_files_cannot_inflate_past_their_directory() {
+        let mut plain = Vec::new();
+        section::write(&mut plain, 1, 0, &sections()).unwrap();
+
+        // the same content, followed by a megabyte of zeros the directory knows nothing of
+        let preamble = section::PREAMBLE_SIZE as usize;
+        let mut file = plain[..preamble].to_vec();
+        file[preamble - 4..].copy_from_slice(&section::FLAG_DEFLATE.to_le_bytes());
+        let mut encoder = ZlibEncoder::new(file, Compression::best());
+        encoder.write_all(&plain[preamble..]).unwrap();
+        encoder.write_all(&vec![0; 1 << 20]).unwrap();
+        let file = encoder.finish().unwrap();
+
+        match Reader::new(Cursor::new(file)) {
+            Err(ReadError::Decompress(_)) => {}
+            other => panic!(
+                "expected a decompression error, got {:?}",
+                other.map(|_| ())
+            ),
+        }
+    }
+}
@@ -0,0 +1,717 @@
+// draws a level as a PNG image entirely on the CPU, so a level can be reviewed without opening
+// LDtk: tiles come from the tileset images of the project, IntGrid cells get the color of their
+// value, entities are drawn as rectangles in the color of their definition, under their sprite
+// when they have one, and decals are drawn transformed. Tile layers are drawn straight from the
+// LDtk level, as the converter does not write them yet
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use {
+    anyhow::{bail, Context, Result},
+    hashbrown::HashMap,
+};
+
+use crate::{
+    info::InfoTables,
+    reader::{Command, Reader},
+    runtime::{LayerInstance, Level, Project, TileInstance},
+};
+
+/// Largest width or height of an image, in pixels.
+pub const MAX_SIZE: u32 = 16384;
+
+/// Color of cells whose value the project does not define.
+const UNKNOWN_COLOR: [u8; 4] = [0xFF, 0x00, 0xFF, 0xFF];
+
+/// Opacity of the inside of entity rectangles, their border is opaque.
+const ENTITY_FILL: u8 = 0x60;
+
+/// An RGBA image, 8 bits per channel, rows top to bottom.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32, color: [u8; 4]) -> Self {
+        Image {
+            width,
+            height,
+            pixels: color
+                .iter()
+                .copied()
+                .cycle()
+                .take(width as usize * height as usize * 4)
+                .collect(),
+        }
+    }
+
+    /// Decodes a PNG image of any color type and bit depth.
+    pub fn read_png<R: Read>(src: R) -> Result<Self> {
+        let mut decoder = png::Decoder::new(src);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data)?;
+        data.truncate(info.buffer_size());
+
+        let pixels = match info.color_type {
+            png::ColorType::Rgba => data,
+            png::ColorType::Rgb => data
+                .chunks_exact(3)
+                .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 0xFF])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => data
+                .chunks_exact(2)
+                .flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]])
+                .collect(),
+            png::ColorType::Grayscale => data.iter().flat_map(|g| [*g, *g, *g, 0xFF]).collect(),
+            png::ColorType::Indexed => bail!("palette was not expanded"),
+        };
+        Ok(Image {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    pub fn write_png<W: Write>(&self, f: W) -> Result<()> {
+        let mut encoder = png::Encoder::new(f, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Blends a color over a single pixel, pixels outside the image are left out.
+    fn blend(&mut self, x: i64, y: i64, color: [u8; 4]) {
+        if x < 0 || y < 0 || x >= i64::from(self.width) || y >= i64::from(self.height) {
+            return;
+        }
+        let index = (y as usize * self.width as usize + x as usize) * 4;
+        let pixel = &mut self.pixels[index..index + 4];
+        let alpha = u32::from(color[3]);
+        for channel in 0..3 {
+            let (src, dst) = (u32::from(color[channel]), u32::from(pixel[channel]));
+            pixel[channel] = ((src * alpha + dst * (255 - alpha)) / 255) as u8;
+        }
+        pixel[3] = (alpha + u32::from(pixel[3]) * (255 - alpha) / 255) as u8;
+    }
+
+    fn fill(&mut self, x: i64, y: i64, width: i64, height: i64, color: [u8; 4]) {
+        for py in y.max(0)..(y + height).min(i64::from(self.height)) {
+            for px in x.max(0)..(x + width).min(i64::from(self.width)) {
+                self.blend(px, py, color);
+            }
+        }
+    }
+
+    fn stroke(&mut self, x: i64, y: i64, width: i64, height: i64, color: [u8; 4]) {
+        if width <= 0 || height <= 0 {
+            return;
+        }
+        self.fill(x, y, width, 1, color);
+        self.fill(x, y + height - 1, width, 1, color);
+        self.fill(x, y + 1, 1, height - 2, color);
+        self.fill(x + width - 1, y + 1, 1, height - 2, color);
+    }
+
+    /// Blends a part of another image over this one, mirrored horizontally and vertically
+    /// as `flip` asks.
+    fn draw(
+        &mut self,
+        src: &Image,
+        (sx, sy): (i64, i64),
+        (w, h): (i64, i64),
+        (x, y): (i64, i64),
+        (flip_x, flip_y): (bool, bool),
+    ) {
+        for dy in 0..h {
+            for dx in 0..w {
+                let px = sx + if flip_x { w - 1 - dx } else { dx };
+                let py = sy + if flip_y { h - 1 - dy } else { dy };
+                if px < 0 || py < 0 || px >= i64::from(src.width) || py >= i64::from(src.height) {
+                    continue;
+                }
+                let index = (py as usize * src.width as usize + px as usize) * 4;
+                let mut color = [0; 4];
+                color.copy_from_slice(&src.pixels[index..index + 4]);
+                self.blend(x + dx, y + dy, color);
+            }
+        }
+    }
+
+    /// Blends a part of another image over this one, scaled and rotated clockwise around its
+    /// center at `(x, y)`, then multiplied by a `0xRRGGBBAA` tint.
+    fn draw_transformed(
+        &mut self,
+        src: &Image,
+        (sx, sy, sw, sh): (i64, i64, i64, i64),
+        (x, y): (i64, i64),
+        (scale_x, scale_y): (f64, f64),
+        degrees: f64,
+        tint: u32,
+    ) {
+        if sw <= 0 || sh <= 0 || scale_x == 0.0 || scale_y == 0.0 {
+            return;
+        }
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let (half_w, half_h) = (
+            sw as f64 * scale_x.abs() / 2.0,
+            sh as f64 * scale_y.abs() / 2.0,
+        );
+        let reach = (half_w * half_w + half_h * half_h).sqrt().ceil() as i64;
+        let tint = tint.to_be_bytes();
+
+        // every pixel the sprite may cover is mapped back into the source rectangle
+        for py in y - reach..=y + reach {
+            for px in x - reach..=x + reach {
+                let (dx, dy) = ((px - x) as f64 + 0.5, (py - y) as f64 + 0.5);
+                let u = (dx * cos + dy * sin) / scale_x + sw as f64 / 2.0;
+                let v = (dy * cos - dx * sin) / scale_y + sh as f64 / 2.0;
+                if u < 0.0 || v < 0.0 || u >= sw as f64 || v >= sh as f64 {
+                    continue;
+                }
+                let (tx, ty) = (sx + u as i64, sy + v as i64);
+                if tx < 0 || ty < 0 || tx >= i64::from(src.width) || ty >= i64::from(src.height) {
+                    continue;
+                }
+                let index = (ty as usize * src.width as usize + tx as usize) * 4;
+                let mut color = [0; 4];
+                for (channel, value) in color.iter_mut().enumerate() {
+                    let texel = u32::from(src.pixels[index + channel]);
+                    *value = (texel * u32::from(tint[channel]) / 255) as u8;
+                }
+                self.blend(px, py, color);
+            }
+        }
+    }
+}
+
+/// Parses an LDtk `#RRGGBB` color.
+pub fn parse_color(color: &str) -> Option<[u8; 4]> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let rgb = u32::from_str_radix(hex, 16).ok()?;
+    Some([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 0xFF])
+}
+
+/// The commands of a single layer, in file order.
+struct Layer {
+    grid_size: u32,
+    name: Option<String>,
+    commands: Vec<Command>,
+}
+
+/// The tiles of an LDtk Tiles or AutoLayer layer, which have no LVL commands yet.
+struct TileLayer<'a> {
+    grid_size: i64,
+
+    /// Path of the tileset image, relative to the project
+    tileset: Option<&'a str>,
+
+    tiles: &'a [TileInstance],
+}
+
+/// A layer of the image, top to bottom.
+enum Source<'a> {
+    Commands(Layer),
+    Tiles(TileLayer<'a>),
+}
+
+/// Draws the level stored in an LVL file. Values are looked up in the info tables, then in
+/// the definitions of the project; tilesets are read relative to `project_dir`.
+///
+/// When the file was converted from `map` with names, the Tiles and AutoLayer layers of `map`,
+/// which LVL files cannot hold yet, are drawn straight from its tiles, among the layers of the
+/// file named after the others.
+///
+/// The image is `size` pixels large when given, otherwise the size the file declares grown
+/// to fit its content. Layers are drawn bottom to top, the first one of the file last, like
+/// LDtk does.
+pub fn level<R: Read>(
+    commands: Reader<R>,
+    project: &Project,
+    tables: &InfoTables,
+    project_dir: &Path,
+    size: Option<(u32, u32)>,
+    background: &str,
+    map: Option<&Level>,
+) -> Result<Image> {
+    let (mut width, mut height) = (0u64, 0u64);
+    let mut strings = Vec::new();
+    let mut layers: Vec<Layer> = Vec::new();
+    for command in commands {
+        match command? {
+            Command::GlobalWidth(w) => width = w,
+            Command::GlobalHeight(h) => height = h,
+            Command::StringTable(table) => strings = table,
+            Command::LayerType(_) => layers.push(Layer {
+                grid_size: 0,
+                name: None,
+                commands: Vec::new(),
+            }),
+            command => match (layers.last_mut(), command) {
+                (Some(layer), Command::LayerWidth(size)) => layer.grid_size = size,
+                (Some(layer), Command::LayerName(index)) => {
+                    layer.name = strings.get(index as usize).cloned()
+                }
+                (Some(layer), command) => layer.commands.push(command),
+                (None, _) => {}
+            },
+        }
+    }
+
+    let layers = match map.and_then(|map| map.layer_instances.as_ref()) {
+        Some(instances) => interleave(project, instances, layers),
+        None => layers.into_iter().map(Source::Commands).collect(),
+    };
+
+    let (width, height) = match size {
+        Some((width, height)) => (u64::from(width), u64::from(height)),
+        None => {
+            for layer in layers.iter() {
+                let (w, h) = extent(layer);
+                width = width.max(w);
+                height = height.max(h);
+            }
+            (width, height)
+        }
+    };
+    if width > u64::from(MAX_SIZE) || height > u64::from(MAX_SIZE) {
+        bail!(
+            "level is {}x{} pixels, larger than the {}x{} an image may be",
+            width,
+            height,
+            MAX_SIZE,
+            MAX_SIZE
+        );
+    }
+
+    let mut image = Image::new(
+        width as u32,
+        height as u32,
+        parse_color(background).unwrap_or([0, 0, 0, 0xFF]),
+    );
+    let mut tilesets = HashMap::new();
+    // top left corner of the last entity, which sprites apply to
+    let mut entity_corner = None;
+    for layer in layers.iter().rev() {
+        let layer = match layer {
+            Source::Commands(layer) => layer,
+            Source::Tiles(layer) => {
+                let atlas = match layer.tileset {
+                    Some(rel_path) => atlas(&mut tilesets, project_dir, rel_path)?,
+                    None => continue,
+                };
+                let grid = layer.grid_size;
+                for tile in layer.tiles {
+                    if let ([x, y], [tile_x, tile_y]) = (tile.px.as_slice(), tile.src.as_slice()) {
+                        let flip = (tile.f & 1 != 0, tile.f & 2 != 0);
+                        image.draw(atlas, (*tile_x, *tile_y), (grid, grid), (*x, *y), flip);
+                    }
+                }
+                continue;
+            }
+        };
+        let grid = i64::from(layer.grid_size);
+        let color = |value: i64| cell_color(project, layer.name.as_deref(), value);
+        for command in layer.commands.iter() {
+            match *command {
+                Command::GridCell { x, y, value } if value != 0 => {
+                    let (x, y) = (i64::from(x) * grid, i64::from(y) * grid);
+                    image.fill(x, y, grid, grid, color(i64::from(value)));
+                }
+
+                Command::CollisionRect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    value,
+                } => {
+                    let (x, y) = (i64::from(x) * grid, i64::from(y) * grid);
+                    let (w, h) = (i64::from(width) * grid, i64::from(height) * grid);
+                    image.fill(x, y, w, h, color(i64::from(value)));
+                }
+
+                Command::Tile {
+                    tileset,
+                    x,
+                    y,
+                    tile_x,
+                    tile_y,
+                } => {
+                    if let Some(rel_path) = tileset_path(tables, tileset) {
+                        image.draw(
+                            atlas(&mut tilesets, project_dir, rel_path)?,
+                            (i64::from(tile_x), i64::from(tile_y)),
+                            (grid, grid),
+                            (i64::from(x), i64::from(y)),
+                            (false, false),
+                        );
+                    }
+                }
+
+                Command::Entity {
+                    entity,
+                    x,
+                    y,
+                    width,
+                    height,
+                    origin_x,
+                    origin_y,
+                    ..
+                } => {
+                    let def = tables
+                        .entity_table
+                        .iter()
+                        .find(|entry| entry.value == i64::from(entity))
+                        .and_then(|entry| {
+                            project
+                                .defs
+                                .entities
+                                .iter()
+                                .find(|def| def.identifier == entry.name)
+                        });
+                    let (w, h) = (i64::from(width), i64::from(height));
+                    let x = i64::from(x) - i64::from(origin_x);
+                    let y = i64::from(y) - i64::from(origin_y);
+                    let color = def
+                        .and_then(|def| parse_color(&def.color))
+                        .unwrap_or(UNKNOWN_COLOR);
+                    image.fill(x, y, w, h, [color[0], color[1], color[2], ENTITY_FILL]);
+                    image.stroke(x, y, w, h, color);
+                    entity_corner = Some((x, y));
+                }
+
+                // drawn at full size whatever the render mode, enough to tell what it is
+                Command::EntitySprite {
+                    tileset,
+                    x,
+                    y,
+                    width,
+                    height,
+                    ..
+                } => {
+                    if let (Some(rel_path), Some(corner)) =
+                        (tileset_path(tables, tileset), entity_corner)
+                    {
+                        image.draw(
+                            atlas(&mut tilesets, project_dir, rel_path)?,
+                            (i64::from(x), i64::from(y)),
+                            (i64::from(width), i64::from(height)),
+                            corner,
+                            (false, false),
+                        );
+                    }
+                }
+
+                Command::Decal {
+                    tileset,
+                    tile_x,
+                    tile_y,
+                    tile_width,
+                    tile_height,
+                    x,
+                    y,
+                    scale_x,
+                    scale_y,
+                    rotation,
+                    tint,
+                } => {
+                    if let Some(rel_path) = tileset_path(tables, tileset) {
+                        image.draw_transformed(
+                            atlas(&mut tilesets, project_dir, rel_path)?,
+                            (
+                                i64::from(tile_x),
+                                i64::from(tile_y),
+                                i64::from(tile_width),
+                                i64::from(tile_height),
+                            ),
+                            (i64::from(x), i64::from(y)),
+                            (f64::from(scale_x), f64::from(scale_y)),
+                            f64::from(rotation),
+                            tint,
+                        );
+                    }
+                }
+
+                _ => {}
+            }
+        }
+    }
+    Ok(image)
+}
+
+/// Puts the layers of a file converted from a level with names back in the order of the
+/// level, along with the tile layers it could not hold. Layers of the file the level does not
+/// name come last.
+fn interleave<'a>(
+    project: &'a Project,
+    instances: &'a [LayerInstance],
+    layers: Vec<Layer>,
+) -> Vec<Source<'a>> {
+    let mut layers = layers.into_iter().map(Some).collect::<Vec<_>>();
+    let mut sources = Vec::new();
+    for instance in instances {
+        match instance.layer_instance_type.as_str() {
+            "Tiles" | "AutoLayer" => {
+                let tileset = instance.tileset_def_uid.and_then(|uid| {
+                    let def = project.defs.tilesets.iter().find(|def| def.uid == uid)?;
+                    def.rel_path.as_deref()
+                });
+                let tiles = match instance.layer_instance_type.as_str() {
+                    "Tiles" => &instance.grid_tiles,
+                    _ => &instance.auto_layer_tiles,
+                };
+                sources.push(Source::Tiles(TileLayer {
+                    grid_size: instance.grid_size,
+                    tileset,
+                    tiles,
+                }));
+            }
+            // decal layers share the name of the entity layer they follow
+            _ => sources.extend(
+                layers
+                    .iter_mut()
+                    .filter(|layer| {
+                        layer.as_ref().and_then(|layer| layer.name.as_deref())
+                            == Some(instance.identifier.as_str())
+                    })
+                    .filter_map(Option::take)
+                    .map(Source::Commands),
+            ),
+        }
+    }
+    sources.extend(layers.into_iter().flatten().map(Source::Commands));
+    sources
+}
+
+/// Size of the content of a layer, in pixels.
+fn extent(layer: &Source) -> (u64, u64) {
+    let layer = match layer {
+        Source::Commands(layer) => layer,
+        Source::Tiles(layer) => {
+            let grid = layer.grid_size.max(0) as u64;
+            return layer
+                .tiles
+                .iter()
+                .filter_map(|tile| match tile.px.as_slice() {
+                    [x, y] => Some(((*x).max(0) as u64 + grid, (*y).max(0) as u64 + grid)),
+                    _ => None,
+                })
+                .fold((0, 0), |(w, h), (x, y)| (w.max(x), h.max(y)));
+        }
+    };
+    let grid = u64::from(layer.grid_size);
+    let cell = |x: i16, y: i16| (x.max(0) as u64 + 1, y.max(0) as u64 + 1);
+    layer
+        .commands
+        .iter()
+        .map(|command| match *command {
+            Command::GridCell { x, y, .. } => {
+                let (x, y) = cell(x, y);
+                (x * grid, y * grid)
+            }
+            Command::CollisionRect {
+                x,
+                y,
+                width,
+                height,
+                ..
+            } => {
+                let (x, y) = cell(x, y);
+                (
+                    (x - 1 + u64::from(width)) * grid,
+                    (y - 1 + u64::from(height)) * grid,
+                )
+            }
+            Command::Tile { x, y, .. } => (u64::from(x) + grid, u64::from(y) + grid),
+            Command::Decal {
+                x,
+                y,
+                tile_width,
+                tile_height,
+                scale_x,
+                scale_y,
+                ..
+            } => (
+                (f64::from(x) + f64::from(tile_width) * f64::from(scale_x.abs()) / 2.0).max(0.0)
+                    as u64,
+                (f64::from(y) + f64::from(tile_height) * f64::from(scale_y.abs()) / 2.0).max(0.0)
+                    as u64,
+            ),
+            Command::Entity {
+                x,
+                y,
+                width,
+                height,
+                origin_x,
+                origin_y,
+                ..
+            } => (
+                (i64::from(x) - i64::from(origin_x)).max(0) as u64 + u64::from(width),
+                (i64::from(y) - i64::from(origin_y)).max(0) as u64 + u64::from(height),
+            ),
+            _ => (0, 0),
+        })
+        .fold((0, 0), |(w, h), (x, y)| (w.max(x), h.max(y)))
+}
+
+/// Color of an IntGrid value, from the named layer when known, otherwise from the first
+/// IntGrid layer defining the value.
+fn cell_color(project: &Project, layer: Option<&str>, value: i64) -> [u8; 4] {
+    let defs = &project.defs.layers;
+    let named = layer.and_then(|name| defs.iter().find(|def| def.identifier == name));
+    named
+        .into_iter()
+        .chain(defs.iter())
+        .flat_map(|def| def.int_grid_values.iter())
+        .find(|def| def.value == value)
+        .and_then(|def| parse_color(&def.color))
+        .unwrap_or(UNKNOWN_COLOR)
+}
+
+/// Path of a tileset image, relative to the project, `None` when the tileset table does not
+/// name it.
+fn tileset_path(tables: &InfoTables, tileset: i32) -> Option<&str> {
+    tables
+        .tileset_table
+        .iter()
+        .find(|entry| entry.value == i64::from(tileset))
+        .map(|entry| entry.name.as_str())
+}
+
+/// The image of a tileset, read the first time it is drawn.
+fn atlas<'a>(
+    tilesets: &'a mut HashMap<String, Image>,
+    project_dir: &Path,
+    rel_path: &str,
+) -> Result<&'a Image> {
+    if !tilesets.contains_key(rel_path) {
+        let image = read_tileset(project_dir, rel_path)?;
+        tilesets.insert(rel_path.to_owned(), image);
+    }
+    Ok(&tilesets[rel_path])
+}
+
+fn read_tileset(project_dir: &Path, rel_path: &str) -> Result<Image> {
+    let path = project_dir.join(rel_path);
+    let src = File::open(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    Image::read_png(BufReader::new(src)).with_context(|| {
+        format!(
+            "failed to decode {}, only PNG tilesets are supported",
+            path.display()
+        )
+    })
+}
+
+/// Writes an image to a PNG file.
+pub fn save(image: &Image, path: &Path) -> Result<()> {
+    let dst = File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    image
+        .write_png(BufWriter::new(dst))
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Cursor};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{convert, runtime::fixtures};
+
+    const GREEN: [u8; 4] = [0x00, 0xFF, 0x00, 0xFF];
+    const BLUE: [u8; 4] = [0x00, 0x00, 0xFF, 0xFF];
+    const RED: [u8; 4] = [0xFF, 0x00, 0x00, 0xFF];
+
+    fn pixel(image: &Image, x: usize, y: usize) -> [u8; 4] {
+        let index = (y * image.width as usize + x) * 4;
+        let mut color = [0; 4];
+        color.copy_from_slice(&image.pixels[index..index + 4]);
+        color
+    }
+
+    /// Renders the only level of a project the way `render --level` does, its tile layers
+    /// left out of the conversion.
+    fn render(project: &Project, project_dir: &Path) -> Image {
+        let map = &project.levels[0];
+        let mut converted = map.clone();
+        if let Some(layers) = &mut converted.layer_instances {
+            layers.retain(|layer| layer.layer_instance_type != "Tiles");
+        }
+        let options = convert::Options {
+            names: true,
+            ..convert::Options::default()
+        };
+        let tables = InfoTables::default();
+        let mut data = Vec::new();
+        convert::level(project, &converted, &tables, &options, &mut data).unwrap();
+
+        let commands = Reader::new(Cursor::new(data)).unwrap();
+        level(
+            commands,
+            project,
+            &tables,
+            project_dir,
+            Some((64, 64)),
+            "#000000",
+            Some(map),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn tile_layers_are_drawn_among_the_others() {
+        // a green tile and a blue one, side by side
+        let dir = std::env::temp_dir().join(format!("cartographer-render-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut tileset = Image::new(32, 16, GREEN);
+        for y in 0..16 {
+            for x in 16..32 {
+                tileset.blend(x, y, BLUE);
+            }
+        }
+        tileset
+            .write_png(File::create(dir.join("tiles.png")).unwrap())
+            .unwrap();
+
+        let mut defs = fixtures::defs();
+        defs["tilesets"] = json!([{ "identifier": "Tiles", "relPath": "tiles.png", "uid": 5 }]);
+        let mut tiles = fixtures::layer("Tiles", "Tiles", 3, 4, 4);
+        tiles["__tilesetDefUid"] = json!(5);
+        tiles["gridTiles"] = json!([
+            { "f": 0, "px": [0, 0], "src": [16, 0], "t": 1 },
+            { "f": 1, "px": [16, 16], "src": [0, 0], "t": 0 }
+        ]);
+        let grid = fixtures::int_grid(4, &[1, 0, 0, 0, 0, 1]);
+
+        // on top of the grid, the blue tile hides its red cell
+        let project = fixtures::project(defs.clone(), vec![tiles.clone(), grid.clone()]);
+        let image = render(&project, &dir);
+        assert_eq!(pixel(&image, 8, 8), BLUE);
+        assert_eq!(pixel(&image, 24, 24), GREEN);
+        assert_eq!(pixel(&image, 40, 8), [0, 0, 0, 0xFF]);
+
+        // under it, only the cells are left to see
+        let project = fixtures::project(defs, vec![grid, tiles]);
+        let image = render(&project, &dir);
+        assert_eq!(pixel(&image, 8, 8), RED);
+        assert_eq!(pixel(&image, 24, 24), RED);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
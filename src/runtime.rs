@@ -0,0 +1,535 @@
+// a trimmed down view of the structures in `ldtk.rs`, it only names the fields the
+// converter reads, serde skips everything else (auto-layer rules, cached pixel data,
+// saved selections, forced refs...) without allocating for it
+
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use {
+    anyhow::{Context, Result},
+    serde::Deserialize,
+};
+
+use crate::compat;
+
+/// Runtime counterpart of `ldtk::LdtkJson`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Project {
+    /// File format version
+    pub json_version: String,
+
+    /// Default background color of levels
+    pub default_level_bg_color: String,
+
+    /// Default new level height
+    pub default_level_height: Option<i64>,
+
+    /// Default new level width
+    pub default_level_width: Option<i64>,
+
+    pub defs: Definitions,
+
+    pub levels: Vec<Level>,
+
+    /// Height of the world grid in pixels, `null` in multi-world projects
+    pub world_grid_height: Option<i64>,
+
+    /// Width of the world grid in pixels, `null` in multi-world projects
+    pub world_grid_width: Option<i64>,
+
+    /// How levels are organized (*Free, GridVania, LinearHorizontal or LinearVertical*),
+    /// `null` in multi-world projects
+    pub world_layout: Option<String>,
+
+    #[serde(default)]
+    pub worlds: Vec<World>,
+}
+
+/// Runtime counterpart of `ldtk::Definitions`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Definitions {
+    pub entities: Vec<EntityDefinition>,
+
+    pub layers: Vec<LayerDefinition>,
+
+    pub tilesets: Vec<TilesetDefinition>,
+}
+
+/// Runtime counterpart of `ldtk::EntityDefinition`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityDefinition {
+    /// Base entity color
+    pub color: String,
+
+    /// Pixel height
+    pub height: i64,
+
+    /// User defined unique identifier
+    pub identifier: String,
+
+    /// An array of 4 dimensions for the up/right/down/left borders (in this order) when using
+    /// 9-slice mode for `tileRenderMode`
+    pub nine_slice_borders: Vec<i64>,
+
+    /// Pivot X coordinate (from 0 to 1.0)
+    pub pivot_x: f64,
+
+    /// Pivot Y coordinate (from 0 to 1.0)
+    pub pivot_y: f64,
+
+    /// An array of strings that classifies this entity
+    pub tags: Vec<String>,
+
+    /// How the entity tile is rendered inside the entity bounds
+    pub tile_render_mode: String,
+
+    /// Unique Int identifier
+    pub uid: i64,
+
+    /// Pixel width
+    pub width: i64,
+}
+
+/// Runtime counterpart of `ldtk::TilesetDefinition`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TilesetDefinition {
+    /// User defined unique identifier
+    pub identifier: String,
+
+    /// Path to the source file, relative to the current project JSON file, `None` for
+    /// embedded atlases
+    pub rel_path: Option<String>,
+
+    /// Unique Int identifier
+    pub uid: i64,
+}
+
+/// Runtime counterpart of `ldtk::LayerDefinition`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LayerDefinition {
+    /// Type of the layer (*IntGrid, Entities, Tiles or AutoLayer*)
+    #[serde(rename = "__type")]
+    pub layer_definition_type: String,
+
+    /// An array of tags to forbid some Entities in this layer
+    pub excluded_tags: Vec<String>,
+
+    /// User defined unique identifier
+    pub identifier: String,
+
+    /// Values of an IntGrid layer, empty for other layer types
+    pub int_grid_values: Vec<IntGridValueDefinition>,
+
+    /// An array of tags to filter Entities that can be added to this layer
+    pub required_tags: Vec<String>,
+
+    /// Unique Int identifier
+    pub uid: i64,
+
+    /// Display tags
+    pub ui_filter_tags: Vec<String>,
+}
+
+/// Runtime counterpart of `ldtk::IntGridValueDefinition`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntGridValueDefinition {
+    pub color: String,
+
+    /// The IntGrid value itself
+    pub value: i64,
+}
+
+/// Runtime counterpart of `ldtk::World`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct World {
+    /// User defined unique identifier
+    pub identifier: String,
+
+    /// Unique instance identifer
+    pub iid: String,
+
+    pub levels: Vec<Level>,
+
+    /// Height of the world grid in pixels
+    pub world_grid_height: i64,
+
+    /// Width of the world grid in pixels
+    pub world_grid_width: i64,
+
+    /// How levels are organized (*Free, GridVania, LinearHorizontal or LinearVertical*)
+    pub world_layout: Option<String>,
+}
+
+/// Runtime counterpart of `ldtk::Level`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Level {
+    /// Background color of the level, the project default when the level has none
+    #[serde(rename = "__bgColor")]
+    pub bg_color: String,
+
+    /// This value is not null if the project option "*Save levels separately*" is enabled. In
+    /// this case, this **relative** path points to the level Json file.
+    pub external_rel_path: Option<String>,
+
+    /// User defined unique identifier
+    pub identifier: String,
+
+    /// Unique instance identifier
+    pub iid: String,
+
+    /// `null` when the level is stored in an external file.
+    pub layer_instances: Option<Vec<LayerInstance>>,
+
+    /// Levels touching this one
+    #[serde(rename = "__neighbours")]
+    pub neighbours: Vec<NeighbourLevel>,
+
+    /// Height of the level in pixels
+    pub px_hei: i64,
+
+    /// Width of the level in pixels
+    pub px_wid: i64,
+
+    /// Unique Int identifier
+    pub uid: i64,
+
+    /// Index that represents the "depth" of the level in the world, greater means "above"
+    pub world_depth: i64,
+
+    /// World X coordinate in pixels
+    pub world_x: i64,
+
+    /// World Y coordinate in pixels
+    pub world_y: i64,
+}
+
+/// Runtime counterpart of `ldtk::NeighbourLevel`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NeighbourLevel {
+    /// Where the neighbour is: `n`orth, `s`outh, `w`est, `e`ast, `<` (lower depth), `>`
+    /// (greater depth) or `o` (overlapping, same depth)
+    pub dir: String,
+
+    /// Neighbour instance identifier
+    pub level_iid: String,
+}
+
+/// Runtime counterpart of `ldtk::LayerInstance`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LayerInstance {
+    /// Grid-based height
+    #[serde(rename = "__cHei")]
+    pub c_hei: i64,
+
+    /// Grid-based width
+    #[serde(rename = "__cWid")]
+    pub c_wid: i64,
+
+    /// Grid size
+    #[serde(rename = "__gridSize")]
+    pub grid_size: i64,
+
+    /// Layer definition identifier
+    #[serde(rename = "__identifier")]
+    pub identifier: String,
+
+    /// The definition UID of corresponding Tileset, if any.
+    #[serde(rename = "__tilesetDefUid")]
+    pub tileset_def_uid: Option<i64>,
+
+    /// The relative path to corresponding Tileset, if any.
+    #[serde(rename = "__tilesetRelPath")]
+    pub tileset_rel_path: Option<String>,
+
+    /// Layer type (possible values: IntGrid, Entities, Tiles or AutoLayer)
+    #[serde(rename = "__type")]
+    pub layer_instance_type: String,
+
+    /// Tiles placed by the rules of an AutoLayer, or an IntGrid layer with rules
+    pub auto_layer_tiles: Vec<TileInstance>,
+
+    pub entity_instances: Vec<EntityInstance>,
+
+    pub grid_tiles: Vec<TileInstance>,
+
+    /// Unique layer instance identifier
+    pub iid: String,
+
+    /// IntGrid values, left to right and top to bottom, `0` means "empty cell".
+    pub int_grid_csv: Vec<i64>,
+
+    /// Reference the Layer definition UID
+    pub layer_def_uid: i64,
+}
+
+/// Runtime counterpart of `ldtk::EntityInstance`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityInstance {
+    /// Entity definition identifier
+    #[serde(rename = "__identifier")]
+    pub identifier: String,
+
+    /// Reference of the **Entity definition** UID
+    pub def_uid: i64,
+
+    /// Array of tags defined in this Entity definition
+    #[serde(rename = "__tags")]
+    pub tags: Vec<String>,
+
+    /// An array of all custom fields and their values.
+    #[serde(default)]
+    pub field_instances: Vec<FieldInstance>,
+
+    /// Entity height in pixels
+    pub height: i64,
+
+    /// Unique instance identifier
+    pub iid: String,
+
+    /// Pivot coordinates (`[x,y]` format, values are from 0 to 1) of the Entity
+    #[serde(rename = "__pivot")]
+    pub pivot: Vec<f64>,
+
+    /// Optional tileset rectangle used to display this entity
+    #[serde(rename = "__tile")]
+    pub tile: Option<TilesetRectangle>,
+
+    /// Pixel coordinates (`[x,y]` format) in current level coordinate space
+    pub px: Vec<i64>,
+
+    /// Entity width in pixels
+    pub width: i64,
+}
+
+/// Runtime counterpart of `ldtk::FieldInstance`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FieldInstance {
+    /// Field definition identifier
+    #[serde(rename = "__identifier")]
+    pub identifier: String,
+
+    /// Actual value of the field instance, `None` when unset
+    #[serde(rename = "__value")]
+    pub value: Option<serde_json::Value>,
+}
+
+/// Runtime counterpart of `ldtk::TilesetRectangle`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TilesetRectangle {
+    /// Height in pixels
+    pub h: i64,
+
+    /// UID of the tileset
+    pub tileset_uid: i64,
+
+    /// Width in pixels
+    pub w: i64,
+
+    /// X pixels coordinate of the top-left corner in the Tileset image
+    pub x: i64,
+
+    /// Y pixels coordinate of the top-left corner in the Tileset image
+    pub y: i64,
+}
+
+/// Runtime counterpart of `ldtk::TileInstance`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TileInstance {
+    /// Flip bits: bit 0 flips the tile horizontally, bit 1 vertically
+    pub f: i64,
+
+    /// Pixel coordinates of the tile in the **layer** (`[x,y]` format)
+    pub px: Vec<i64>,
+
+    /// Pixel coordinates of the tile in the **tileset** (`[x,y]` format)
+    pub src: Vec<i64>,
+
+    /// The *Tile ID* in the corresponding tileset.
+    pub t: i64,
+}
+
+/// The file a level saved separately from its project is stored in.
+pub struct ExternalLevel {
+    pub path: PathBuf,
+    pub raw: String,
+}
+
+impl ExternalLevel {
+    /// Reads the file holding the layers of a level, `None` when they are already in the
+    /// project at `project_path`.
+    pub fn read(project_path: &Path, level: &Level) -> Result<Option<Self>> {
+        let rel_path = match (&level.layer_instances, &level.external_rel_path) {
+            (None, Some(rel_path)) => rel_path,
+            _ => return Ok(None),
+        };
+        let dir = project_path.parent().unwrap_or_else(|| Path::new("."));
+        let path = dir.join(rel_path);
+        let raw = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Ok(Some(ExternalLevel { path, raw }))
+    }
+
+    /// Parses the level into either model of the project it belongs to.
+    pub fn parse<P: compat::Project>(&self, project: &P) -> Result<P::Level> {
+        compat::parse_level(&self.raw, project)
+            .with_context(|| format!("failed to parse {}", self.path.display()))
+    }
+}
+
+/// A level of the project at `project_path` with its layer instances loaded, read from its
+/// own file when it was saved separately.
+pub fn load_level<'a>(
+    project_path: &Path,
+    project: &Project,
+    level: &'a Level,
+) -> Result<Cow<'a, Level>> {
+    match ExternalLevel::read(project_path, level)? {
+        Some(external) => Ok(Cow::Owned(external.parse(project)?)),
+        None => Ok(Cow::Borrowed(level)),
+    }
+}
+
+/// Small projects built in memory, for tests.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use serde_json::{json, Value};
+
+    use super::Project;
+
+    /// Definitions with an IntGrid layer `Grid` (values 1 and 2) and an Entities layer
+    /// `Entities`, and no entity or tileset.
+    pub fn defs() -> Value {
+        json!({
+            "entities": [],
+            "layers": [
+                {
+                    "__type": "IntGrid",
+                    "excludedTags": [],
+                    "identifier": "Grid",
+                    "intGridValues": [
+                        { "color": "#FF0000", "value": 1 },
+                        { "color": "#0000FF", "value": 2 }
+                    ],
+                    "requiredTags": [],
+                    "uid": 1,
+                    "uiFilterTags": []
+                },
+                {
+                    "__type": "Entities",
+                    "excludedTags": [],
+                    "identifier": "Entities",
+                    "intGridValues": [],
+                    "requiredTags": [],
+                    "uid": 2,
+                    "uiFilterTags": []
+                }
+            ],
+            "tilesets": []
+        })
+    }
+
+    /// A project with `defs` as its definitions and a single 64x64 level `Level_0` holding
+    /// `layers`, top to bottom.
+    pub fn project(defs: Value, layers: Vec<Value>) -> Project {
+        serde_json::from_value(project_json(defs, vec![level("Level_0", 0, layers)])).unwrap()
+    }
+
+    /// The JSON of a project with `defs` as its definitions and `levels` at its root.
+    pub fn project_json(defs: Value, levels: Vec<Value>) -> Value {
+        json!({
+            "jsonVersion": "1.5.3",
+            "defaultLevelBgColor": "#000000",
+            "defaultLevelWidth": 64,
+            "defaultLevelHeight": 64,
+            "defs": defs,
+            "levels": levels,
+            "worldGridWidth": 64,
+            "worldGridHeight": 64,
+            "worldLayout": "Free"
+        })
+    }
+
+    /// A 64x64 level holding `layers`, top to bottom.
+    pub fn level(identifier: &str, uid: i64, layers: Vec<Value>) -> Value {
+        json!({
+            "__bgColor": "#000000",
+            "externalRelPath": null,
+            "identifier": identifier,
+            "iid": format!("level-{}", uid),
+            "layerInstances": layers,
+            "__neighbours": [],
+            "pxWid": 64,
+            "pxHei": 64,
+            "uid": uid,
+            "worldDepth": 0,
+            "worldX": 64 * uid,
+            "worldY": 0
+        })
+    }
+
+    /// A layer instance of 16 pixel cells, `width` cells wide, with nothing in it.
+    pub fn layer(identifier: &str, typ: &str, def_uid: i64, width: i64, height: i64) -> Value {
+        json!({
+            "__cWid": width,
+            "__cHei": height,
+            "__gridSize": 16,
+            "__identifier": identifier,
+            "__tilesetDefUid": null,
+            "__tilesetRelPath": null,
+            "__type": typ,
+            "autoLayerTiles": [],
+            "entityInstances": [],
+            "gridTiles": [],
+            "iid": format!("layer-{}", identifier),
+            "intGridCsv": [],
+            "layerDefUid": def_uid
+        })
+    }
+
+    /// An instance of the `Entities` layer of `defs`, 4x4 cells, holding `entities`.
+    pub fn entities(entities: Vec<Value>) -> Value {
+        let mut layer = layer("Entities", "Entities", 2, 4, 4);
+        layer["entityInstances"] = json!(entities);
+        layer
+    }
+
+    /// A 16x16 instance of the entity `identifier` at `(x, y)`, its pivot in its top left
+    /// corner, with the given `__tags` and `fieldInstances`.
+    pub fn entity(identifier: &str, (x, y): (i64, i64), tags: &[&str], fields: Value) -> Value {
+        json!({
+            "__identifier": identifier,
+            "__pivot": [0.0, 0.0],
+            "__tags": tags,
+            "__tile": null,
+            "defUid": 3,
+            "fieldInstances": fields,
+            "height": 16,
+            "iid": format!("{}-{}-{}", identifier, x, y),
+            "px": [x, y],
+            "width": 16
+        })
+    }
+
+    /// An instance of the `Grid` layer of `defs`, `width` cells wide.
+    pub fn int_grid(width: i64, cells: &[i64]) -> Value {
+        let height = (cells.len() as i64 + width - 1) / width;
+        let mut layer = layer("Grid", "IntGrid", 1, width, height);
+        layer["intGridCsv"] = json!(cells);
+        layer
+    }
+}
@@ -0,0 +1,271 @@
+// the section directory of LVL Format 1: the same commands as Format 0, grouped into a
+// metadata section and one section per layer, with a directory up front recording where each
+// one starts so a reader can seek straight to the layer it needs
+
+use std::{
+    convert::TryFrom,
+    fmt,
+    io::{self, Write},
+};
+
+use {
+    byteorder::{LittleEndian, WriteBytesExt},
+    flate2::{write::ZlibEncoder, Compression},
+};
+
+use crate::{checksum, global, reader};
+
+/// Section holding the global properties of the level.
+pub const METADATA: u8 = 0;
+
+/// Section holding a single layer, its properties followed by its content.
+pub const LAYER: u8 = 1;
+
+/// Section holding the navigation graph of the IntGrid layer right before it.
+pub const NAVIGATION: u8 = 2;
+
+/// Header flag set when the file ends with a checksum command.
+pub const FLAG_CHECKSUM: u32 = 0x1;
+
+/// Header flag set when everything after the flags is compressed as a single zlib stream.
+pub const FLAG_DEFLATE: u32 = 0x2;
+
+/// Header flags this version of the format knows about.
+pub const FLAGS: u32 = FLAG_CHECKSUM | FLAG_DEFLATE;
+
+/// Size of the part of a Format 1 file that is never compressed: magic bytes and flags.
+pub const PREAMBLE_SIZE: u64 = 13 + 4;
+
+/// Size of the fixed part of a Format 1 header: magic bytes, flags and section count.
+pub const HEADER_SIZE: u64 = 13 + 4 + 4;
+
+/// Size of a single directory entry.
+pub const ENTRY_SIZE: u64 = 1 + 1 + 4 + 4;
+
+/// The commands of one section, before they are laid out in a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub kind: u8,
+
+    /// Layer type of a `LAYER` section, `4` (unknown) otherwise
+    pub layer_type: u8,
+
+    pub data: Vec<u8>,
+}
+
+/// Where a section is stored, as recorded in the directory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Entry {
+    pub kind: u8,
+    pub layer_type: u8,
+
+    /// Offset of the first command from the start of the file
+    pub offset: u32,
+
+    /// Length of the section in bytes
+    pub length: u32,
+}
+
+impl Section {
+    pub fn metadata(data: Vec<u8>) -> Self {
+        Section {
+            kind: METADATA,
+            layer_type: 4,
+            data,
+        }
+    }
+
+    pub fn layer(layer_type: u8, data: Vec<u8>) -> Self {
+        Section {
+            kind: LAYER,
+            layer_type,
+            data,
+        }
+    }
+
+    pub fn navigation(data: Vec<u8>) -> Self {
+        Section {
+            kind: NAVIGATION,
+            layer_type: 4,
+            data,
+        }
+    }
+}
+
+/// Writes a complete LVL file of the given format out of its sections. Format 0 simply
+/// concatenates them after the header, and only knows about `FLAG_CHECKSUM` as it has no
+/// header flags.
+pub fn write<W: Write>(f: &mut W, format: u8, flags: u32, sections: &[Section]) -> io::Result<()> {
+    // the whole file is assembled first, both the checksum and compression need all of it
+    let mut file = Vec::new();
+    match format {
+        0 if flags & !FLAG_CHECKSUM == 0 => {
+            global::write_header(&mut file)?;
+        }
+
+        0 => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "LVL format 0 files can only have a checksum, write format 1 instead",
+            ))
+        }
+
+        1 => {
+            let too_large = || io::Error::new(io::ErrorKind::InvalidData, "level is too large");
+
+            file.write_all(global::HEADER_1)?;
+            file.write_u32::<LittleEndian>(flags)?; // flags
+            file.write_u32::<LittleEndian>(
+                u32::try_from(sections.len()).map_err(|_| too_large())?,
+            )?; // section count
+
+            let mut offset = HEADER_SIZE + ENTRY_SIZE * sections.len() as u64;
+            for section in sections {
+                let length = section.data.len() as u64;
+                file.write_u8(section.kind)?; // section type
+                file.write_u8(section.layer_type)?; // layer type
+                file.write_u32::<LittleEndian>(u32::try_from(offset).map_err(|_| too_large())?)?; // offset
+                file.write_u32::<LittleEndian>(u32::try_from(length).map_err(|_| too_large())?)?; // length
+                offset += length;
+            }
+        }
+
+        format => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("LVL format {} is not supported", format),
+            ))
+        }
+    }
+
+    for section in sections {
+        file.write_all(&section.data)?;
+    }
+    if flags & FLAG_CHECKSUM != 0 {
+        let crc = checksum::crc32(&file);
+        global::set_checksum(&mut file, crc)?;
+    }
+
+    if flags & FLAG_DEFLATE != 0 {
+        // the header and flags stay readable, so readers know to decompress the rest
+        let (preamble, rest) = file.split_at(PREAMBLE_SIZE as usize);
+        f.write_all(preamble)?;
+        let mut encoder = ZlibEncoder::new(f, Compression::best());
+        encoder.write_all(rest)?;
+        encoder.finish()?;
+    } else {
+        f.write_all(&file)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            METADATA => write!(f, "metadata"),
+            LAYER => write!(f, "{} layer", reader::layer_type_name(self.layer_type)),
+            NAVIGATION => write!(f, "navigation"),
+            kind => write!(f, "unknown section type {}", kind),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        global, grid, layer,
+        reader::{self, Command, Reader},
+    };
+
+    fn sections() -> Vec<Section> {
+        let mut metadata = Vec::new();
+        global::set_width(&mut metadata, 48).unwrap();
+        global::set_height(&mut metadata, 32).unwrap();
+        let mut grid = Vec::new();
+        layer::set_type(&mut grid, 1).unwrap();
+        grid::cell_set(&mut grid, 2, 1, 5).unwrap();
+        let mut tiles = Vec::new();
+        layer::set_type(&mut tiles, 0).unwrap();
+        vec![
+            Section::metadata(metadata),
+            Section::layer(1, grid),
+            Section::layer(0, tiles),
+        ]
+    }
+
+    fn commands() -> Vec<Command> {
+        vec![
+            Command::GlobalWidth(48),
+            Command::GlobalHeight(32),
+            Command::LayerType(1),
+            Command::GridCell {
+                x: 2,
+                y: 1,
+                value: 5,
+            },
+            Command::LayerType(0),
+        ]
+    }
+
+    #[test]
+    fn directory_records_where_each_section_is() {
+        let sections = sections();
+        let mut file = Vec::new();
+        write(&mut file, 1, 0, &sections).unwrap();
+
+        let reader = Reader::new(Cursor::new(file.clone())).unwrap();
+        assert_eq!(reader.format(), 1);
+        assert_eq!(reader.flags(), 0);
+        let mut offset = (HEADER_SIZE + ENTRY_SIZE * 3) as u32;
+        for (entry, section) in reader.sections().iter().zip(&sections) {
+            assert_eq!(entry.kind, section.kind);
+            assert_eq!(entry.layer_type, section.layer_type);
+            assert_eq!(entry.offset, offset);
+            assert_eq!(entry.length as usize, section.data.len());
+            let start = offset as usize;
+            assert_eq!(&file[start..start + section.data.len()], &section.data[..]);
+            offset += entry.length;
+        }
+        assert_eq!(offset as usize, file.len());
+    }
+
+    #[test]
+    fn sections_read_back_on_their_own() {
+        for &flags in &[0, FLAG_CHECKSUM, FLAG_DEFLATE, FLAGS] {
+            let mut file = Vec::new();
+            write(&mut file, 1, flags, &sections()).unwrap();
+
+            let mut reader = Reader::new(Cursor::new(file)).unwrap();
+            assert_eq!(reader.flags(), flags);
+            assert_eq!(reader.sections().len(), 3);
+            assert_eq!(reader.read_section(2).unwrap(), [Command::LayerType(0)]);
+            assert_eq!(reader.read_section(0).unwrap(), commands()[..2]);
+            assert_eq!(reader.read_section(1).unwrap(), commands()[2..4]);
+            assert!(reader.read_section(3).is_err());
+        }
+    }
+
+    #[test]
+    fn formats_hold_the_same_commands() {
+        for &format in &[0, 1] {
+            let mut file = Vec::new();
+            write(&mut file, format, FLAG_CHECKSUM, &sections()).unwrap();
+            assert!(reader::verify(&file).unwrap().is_some());
+
+            let found = reader::read_all(Cursor::new(file)).unwrap();
+            assert_eq!(found.len(), 6);
+            assert_eq!(found[..5], commands()[..]);
+            assert!(matches!(found[5], Command::Checksum(_)));
+        }
+    }
+
+    #[test]
+    fn format_0_has_no_room_for_compression() {
+        let mut file = Vec::new();
+        assert!(write(&mut file, 0, FLAG_DEFLATE, &sections()).is_err());
+        assert!(write(&mut file, 2, 0, &sections()).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    convert::TryFrom,
+    io::{Error, ErrorKind, Result, Write},
+};
+
+use {
+    byteorder::{LittleEndian, WriteBytesExt},
+    hashbrown::HashMap,
+};
+
+/// Every distinct string of a level, written once and referenced by index from other commands.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StringTable {
+    strings: Vec<String>,
+    indices: HashMap<String, u32>,
+}
+
+impl StringTable {
+    pub fn new() -> Self {
+        StringTable::default()
+    }
+
+    /// Index of `string` in the table, adding it if it is not there yet. Fails once the table
+    /// holds as many strings as a u32 index can reach.
+    pub fn insert(&mut self, string: &str) -> Result<u32> {
+        if let Some(index) = self.indices.get(string) {
+            return Ok(*index);
+        }
+        let index = u32::try_from(self.strings.len()).map_err(|_| too_large())?;
+        self.strings.push(string.to_owned());
+        self.indices.insert(string.to_owned(), index);
+        Ok(index)
+    }
+
+    pub fn get(&self, index: u32) -> Option<&str> {
+        self.strings.get(index as usize).map(String::as_str)
+    }
+
+    pub fn strings(&self) -> &[String] {
+        &self.strings
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+pub fn table<W: Write>(f: &mut W, table: &StringTable) -> Result<()> {
+    f.write_u8(0x6)?; // string table instruction
+    f.write_u32::<LittleEndian>(u32::try_from(table.strings.len()).map_err(|_| too_large())?)?; // string count
+    for string in table.strings.iter() {
+        f.write_u32::<LittleEndian>(u32::try_from(string.len()).map_err(|_| too_large())?)?; // length in bytes
+        f.write_all(string.as_bytes())?; // utf-8 content
+    }
+    Ok(())
+}
+
+fn too_large() -> Error {
+    Error::new(ErrorKind::InvalidData, "string table is too large")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader;
+
+    #[test]
+    fn strings_are_stored_once() {
+        let mut strings = StringTable::new();
+        assert!(strings.is_empty());
+        assert_eq!(strings.insert("Level_0").unwrap(), 0);
+        assert_eq!(strings.insert("Walls").unwrap(), 1);
+        assert_eq!(strings.insert("Level_0").unwrap(), 0);
+        assert_eq!(strings.insert("").unwrap(), 2);
+        assert_eq!(strings.strings(), ["Level_0", "Walls", ""]);
+        assert_eq!(strings.get(1), Some("Walls"));
+        assert_eq!(strings.get(3), None);
+    }
+
+    #[test]
+    fn tables_read_back_as_written() {
+        let mut strings = StringTable::new();
+        for string in &["Level_0", "Ünïcode ✓", "", "Level_0"] {
+            strings.insert(string).unwrap();
+        }
+        let mut data = Vec::new();
+        table(&mut data, &strings).unwrap();
+        assert_eq!(data[0], 0x6);
+
+        let (read, len) = reader::read_strings(&mut &data[1..], 1).unwrap();
+        assert_eq!(read, strings.strings());
+        assert_eq!(len, data.len() as u64 - 1);
+
+        // a length running past the end of the data is refused
+        data.truncate(data.len() - 1);
+        assert!(reader::read_strings(&mut &data[1..], 1).is_err());
+    }
+}
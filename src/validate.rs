@@ -0,0 +1,296 @@
+// checks a project converts cleanly without writing anything: the project and its external
+// levels are checked against the full schema in `ldtk.rs`, every level goes through a dry
+// run of the converter, and the info table is cross-checked against the project definitions
+
+use std::{
+    borrow::Cow,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    compat, convert, info, ldtk,
+    runtime::{ExternalLevel, Project},
+};
+
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Number of levels checked
+    pub levels: usize,
+
+    /// Problems that make a conversion fail
+    pub errors: Vec<anyhow::Error>,
+
+    /// Data a conversion would silently leave out
+    pub warnings: Vec<String>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Validates a project and its info tables, as converted with `options`. Only failing to read
+/// them is returned as an error, everything wrong with their contents ends up in the report.
+pub fn project(
+    project_path: &Path,
+    info_table_paths: &[PathBuf],
+    options: &convert::Options,
+) -> Result<Report> {
+    let raw = fs::read_to_string(project_path)
+        .with_context(|| format!("failed to read {}", project_path.display()))?;
+    let tables = info::read_all(info_table_paths)?;
+
+    let mut report = Report::default();
+
+    // the strict model refuses anything the schema does not allow, the runtime view is what
+    // actually gets converted
+    let full: ldtk::LdtkJson = match compat::parse_project(&raw) {
+        Ok(full) => full,
+        Err(err) => {
+            report.errors.push(anyhow::Error::new(err).context(format!(
+                "{} does not match the schema",
+                project_path.display()
+            )));
+            return Ok(report);
+        }
+    };
+    let project: Project = compat::parse_project(&raw)?;
+
+    check_tables(&full, &tables, &mut report);
+    check_tags(&full, &tables, options, &mut report);
+    for rule in options.layers.rules() {
+        if !project
+            .defs
+            .layers
+            .iter()
+            .any(|def| rule.matches_definition(def))
+        {
+            report
+                .warnings
+                .push(format!("layer rule `{}` matches no layer definition", rule));
+        }
+    }
+
+    let levels = project
+        .levels
+        .iter()
+        .chain(project.worlds.iter().flat_map(|world| world.levels.iter()));
+    for level in levels {
+        report.levels += 1;
+
+        // external levels are checked against the schema as well
+        let loaded = ExternalLevel::read(project_path, level).and_then(|external| match external {
+            Some(external) => {
+                external.parse(&full)?;
+                Ok(Cow::Owned(external.parse(&project)?))
+            }
+            None => Ok(Cow::Borrowed(level)),
+        });
+        let level = match loaded {
+            Ok(level) => level,
+            Err(err) => {
+                report
+                    .errors
+                    .push(err.context(format!("level `{}`", level.identifier)));
+                continue;
+            }
+        };
+
+        if let Err(err) = convert::level(&project, &level, &tables, options, &mut io::sink()) {
+            report.errors.push(err.into());
+        }
+    }
+
+    Ok(report)
+}
+
+/// Warns about definitions the info table has no entry for, and entries that match nothing.
+fn check_tables(project: &ldtk::LdtkJson, tables: &info::InfoTables, report: &mut Report) {
+    let entities = &project.defs.entities;
+    for def in entities.iter() {
+        if !tables
+            .entity_table
+            .iter()
+            .any(|entry| entry.name == def.identifier)
+        {
+            report.warnings.push(format!(
+                "entity `{}` has no entry in the entity table, its instances are left out",
+                def.identifier
+            ));
+        }
+    }
+    for (index, entry) in tables.entity_table.iter().enumerate() {
+        if !entities.iter().any(|def| def.identifier == entry.name) {
+            report.warnings.push(format!(
+                "entity table entry `{}` matches no entity definition",
+                entry.name
+            ));
+        }
+        if let Some(other) = tables.entity_table[..index]
+            .iter()
+            .find(|other| other.value == entry.value)
+        {
+            report.warnings.push(format!(
+                "entity table entries `{}` and `{}` share the value {}",
+                other.name, entry.name, entry.value
+            ));
+        }
+    }
+
+    let tilesets = &project.defs.tilesets;
+    for def in tilesets.iter() {
+        // embedded atlases have no path to match against
+        if let Some(rel_path) = &def.rel_path {
+            if !tables
+                .tileset_table
+                .iter()
+                .any(|entry| &entry.name == rel_path)
+            {
+                report.warnings.push(format!(
+                    "tileset `{}` ({}) has no entry in the tileset table, its tiles are left out",
+                    def.identifier, rel_path
+                ));
+            }
+        }
+    }
+    for entry in tables.tileset_table.iter() {
+        if !tilesets
+            .iter()
+            .any(|def| def.rel_path.as_ref() == Some(&entry.name))
+        {
+            report.warnings.push(format!(
+                "tileset table entry `{}` matches no tileset definition",
+                entry.name
+            ));
+        }
+    }
+
+    let grids = project
+        .defs
+        .layers
+        .iter()
+        .filter(|def| def.layer_definition_type == "IntGrid")
+        .collect::<Vec<_>>();
+    let collision = &tables.collision;
+    check_grid_values(
+        &grids,
+        ("collision", "solid"),
+        (&collision.layers, &collision.values),
+        |name| collision.applies_to(name),
+        report,
+    );
+    let outline = &tables.outline;
+    check_grid_values(
+        &grids,
+        ("outline", "outlined"),
+        (&outline.layers, &outline.values),
+        |name| outline.applies_to(name),
+        report,
+    );
+    if outline.tolerance < 0.0 {
+        report.warnings.push(format!(
+            "outline tolerance {} is negative, outlines are not simplified",
+            outline.tolerance
+        ));
+    }
+    let navigation = &tables.navigation;
+    check_grid_values(
+        &grids,
+        ("navigation", "walkable"),
+        (&navigation.layers, &navigation.values),
+        |name| navigation.applies_to(name),
+        report,
+    );
+}
+
+/// Warns about IntGrid layers an info table setting names but the project does not define,
+/// and values none of the layers it applies to define. `names` is what the setting and its
+/// values are called in warnings.
+fn check_grid_values(
+    grids: &[&ldtk::LayerDefinition],
+    names: (&str, &str),
+    (layers, values): (&[String], &[i64]),
+    applies_to: impl Fn(&str) -> bool,
+    report: &mut Report,
+) {
+    let (setting, value_name) = names;
+    for name in layers.iter() {
+        if !grids.iter().any(|def| &def.identifier == name) {
+            report.warnings.push(format!(
+                "{} layer `{}` matches no IntGrid layer definition",
+                setting, name
+            ));
+        }
+    }
+    for value in values.iter() {
+        let defined = grids
+            .iter()
+            .filter(|def| applies_to(&def.identifier))
+            .any(|def| def.int_grid_values.iter().any(|v| v.value == *value));
+        if !defined {
+            report.warnings.push(format!(
+                "{} value {} is not defined by any {} layer",
+                value_name, value, setting
+            ));
+        }
+    }
+}
+
+/// Warns about entity tags the filters, decals or tag table name but no entity has, and tags
+/// the tag table leaves out.
+fn check_tags(
+    project: &ldtk::LdtkJson,
+    tables: &info::InfoTables,
+    options: &convert::Options,
+    report: &mut Report,
+) {
+    let entities = &project.defs.entities;
+    let is_used = |tag: &str| entities.iter().any(|def| def.tags.iter().any(|t| t == tag));
+
+    let filters = [&tables.entity_filter, &options.entities];
+    let filtered = filters
+        .iter()
+        .flat_map(|filter| filter.include_tags.iter().chain(filter.exclude_tags.iter()));
+    for tag in filtered {
+        if !is_used(tag) {
+            report
+                .warnings
+                .push(format!("entity filter tag `{}` is used by no entity", tag));
+        }
+    }
+
+    let decals = &tables.decals;
+    for tag in decals.tags.iter() {
+        if !is_used(tag) {
+            report
+                .warnings
+                .push(format!("decal tag `{}` is used by no entity", tag));
+        }
+    }
+
+    if tables.tag_table.is_empty() {
+        return;
+    }
+    for def in entities.iter() {
+        for tag in def.tags.iter() {
+            if !tables.tag_table.iter().any(|entry| &entry.name == tag) {
+                report.warnings.push(format!(
+                    "tag `{}` of entity `{}` has no entry in the tag table, it is left out",
+                    tag, def.identifier
+                ));
+            }
+        }
+    }
+    for entry in tables.tag_table.iter() {
+        if !is_used(&entry.name) {
+            report.warnings.push(format!(
+                "tag table entry `{}` is used by no entity",
+                entry.name
+            ));
+        }
+    }
+}
@@ -0,0 +1,128 @@
+// polls the inputs of a batch conversion and re-runs it whenever one of them changes, the
+// cache manifest makes sure only the levels whose inputs changed are converted again
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use {anyhow::Result, serde::Deserialize};
+
+use crate::batch::{self, Outcome};
+
+/// State of a watched file, `None` when it does not exist (yet).
+type Stamp = Option<(SystemTime, u64)>;
+
+/// Runs a batch conversion, then keeps re-running it each time the project, one of its
+/// external level files or an info table changes on disk. `report` is called with the
+/// result of every run.
+///
+/// Files are polled every `interval` rather than watched through OS notifications, so this
+/// works on any platform and file system. A change is only acted upon once the files stop
+/// changing for a full interval, as LDtk saves the project and each level file one after
+/// the other.
+pub fn run(
+    project_path: &Path,
+    info_table_paths: &[PathBuf],
+    output_dir: &Path,
+    options: &batch::Options,
+    interval: Duration,
+    mut report: impl FnMut(Result<Vec<Outcome>>),
+) -> ! {
+    let mut files = watched_files(project_path, info_table_paths);
+    let mut stamps = snapshot(&files);
+    report(batch::run(
+        project_path,
+        info_table_paths,
+        output_dir,
+        options,
+    ));
+
+    loop {
+        thread::sleep(interval);
+
+        let current = snapshot(&files);
+        if current == stamps {
+            continue;
+        }
+
+        // wait for the editor to finish saving
+        stamps = current;
+        loop {
+            thread::sleep(interval);
+            let current = snapshot(&files);
+            if current == stamps {
+                break;
+            }
+            stamps = current;
+        }
+
+        report(batch::run(
+            project_path,
+            info_table_paths,
+            output_dir,
+            options,
+        ));
+
+        // levels may have been added, removed or moved to external files
+        files = watched_files(project_path, info_table_paths);
+        stamps = snapshot(&files);
+    }
+}
+
+/// The project, info tables and every external level file the project references.
+fn watched_files(project_path: &Path, info_table_paths: &[PathBuf]) -> Vec<PathBuf> {
+    #[derive(Deserialize)]
+    struct Project {
+        #[serde(default)]
+        levels: Vec<Level>,
+        #[serde(default)]
+        worlds: Vec<World>,
+    }
+
+    #[derive(Deserialize)]
+    struct World {
+        #[serde(default)]
+        levels: Vec<Level>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Level {
+        external_rel_path: Option<String>,
+    }
+
+    let mut files = vec![project_path.to_owned()];
+    files.extend_from_slice(info_table_paths);
+
+    // a project caught mid-save is simply watched again on the next change
+    let project = fs::read_to_string(project_path)
+        .ok()
+        .and_then(|raw| serde_json::from_str::<Project>(&raw).ok());
+    if let Some(project) = project {
+        let dir = project_path.parent().unwrap_or_else(|| Path::new("."));
+        let levels = project
+            .levels
+            .iter()
+            .chain(project.worlds.iter().flat_map(|world| world.levels.iter()));
+        files.extend(
+            levels
+                .filter_map(|level| level.external_rel_path.as_ref())
+                .map(|rel_path| dir.join(rel_path)),
+        );
+    }
+
+    files
+}
+
+fn snapshot(files: &[PathBuf]) -> Vec<Stamp> {
+    files
+        .iter()
+        .map(|path| {
+            let metadata = fs::metadata(path).ok()?;
+            Some((metadata.modified().ok()?, metadata.len()))
+        })
+        .collect()
+}
@@ -0,0 +1,405 @@
+// the layout of a world: where each level sits, how large it is and which levels it touches
+// in each direction, so a game can walk from room to room without hardcoding its exits
+
+use std::{
+    convert::TryFrom,
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use {
+    anyhow::{Context, Result},
+    byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt},
+};
+
+use crate::{
+    batch, compat,
+    error::ReadError,
+    reader,
+    runtime::{Level, Project},
+    string::{self, StringTable},
+};
+
+/// Magic bytes every world map starts with.
+pub const HEADER: &[u8] = b"LVL World 0.";
+
+/// File extension of world maps.
+pub const EXTENSION: &str = "lvw";
+
+/// World layouts, in the order of their operand value.
+pub const LAYOUTS: &[&str] = &["Free", "GridVania", "LinearHorizontal", "LinearVertical"];
+
+/// Neighbour directions as LDtk names them, in the order of their operand value.
+pub const DIRECTIONS: &[&str] = &["n", "e", "s", "w", "ne", "se", "sw", "nw", "<", ">", "o"];
+
+/// Operand of a layout or direction LDtk has no name for, or that is missing.
+pub const UNKNOWN: u8 = 0xFF;
+
+/// A world of a project along with its levels.
+#[derive(Debug, Clone)]
+pub struct World<'a> {
+    pub identifier: String,
+
+    /// How levels are organized, one of `LAYOUTS`
+    pub layout: Option<&'a str>,
+
+    /// Size of a GridVania cell in pixels
+    pub grid_width: i64,
+    pub grid_height: i64,
+
+    pub levels: &'a [Level],
+}
+
+/// A world map as read back from a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldMap {
+    pub identifier: String,
+
+    /// Index into `LAYOUTS`, `UNKNOWN` if the project has none
+    pub layout: u8,
+
+    pub grid_width: u32,
+    pub grid_height: u32,
+
+    pub levels: Vec<MapLevel>,
+}
+
+/// A level of a world map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapLevel {
+    pub identifier: String,
+    pub iid: String,
+
+    /// World coordinates of the top left corner of the level, in pixels
+    pub world_x: i32,
+    pub world_y: i32,
+    pub world_depth: i32,
+
+    /// Size of the level in pixels
+    pub width: u32,
+    pub height: u32,
+
+    pub neighbours: Vec<Neighbour>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighbour {
+    /// Index into `DIRECTIONS`, `UNKNOWN` for directions added by later LDtk versions
+    pub direction: u8,
+
+    /// Index of the neighbour in the levels of the map
+    pub level: u32,
+}
+
+/// Splits the levels of a project by world. Projects without multi-worlds have a single world
+/// made of their root levels, named after the project file.
+pub fn worlds<'a>(project_path: &Path, project: &'a Project) -> Vec<World<'a>> {
+    let mut worlds = Vec::new();
+    if !project.levels.is_empty() {
+        let identifier = project_path
+            .file_stem()
+            .map_or_else(|| "world".into(), |stem| stem.to_string_lossy());
+        worlds.push(World {
+            identifier: identifier.into_owned(),
+            layout: project.world_layout.as_deref(),
+            grid_width: project.world_grid_width.unwrap_or(0),
+            grid_height: project.world_grid_height.unwrap_or(0),
+            levels: &project.levels,
+        });
+    }
+    for world in project.worlds.iter() {
+        worlds.push(World {
+            identifier: world.identifier.clone(),
+            layout: world.world_layout.as_deref(),
+            grid_width: world.world_grid_width,
+            grid_height: world.world_grid_height,
+            levels: &world.levels,
+        });
+    }
+    worlds
+}
+
+/// Writes the map of a world. Neighbours outside the world are left out.
+pub fn write<W: Write>(f: &mut W, world: &World) -> io::Result<()> {
+    let out_of_range = |field: &str, name: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("`{}` of `{}` is out of range", field, name),
+        )
+    };
+    let too_large = || io::Error::new(io::ErrorKind::InvalidData, "world is too large");
+
+    let mut strings = StringTable::new();
+    let identifier = strings.insert(&world.identifier)?;
+    let names = world
+        .levels
+        .iter()
+        .map(|level| {
+            Ok((
+                strings.insert(&level.identifier)?,
+                strings.insert(&level.iid)?,
+            ))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let layout = world
+        .layout
+        .and_then(|layout| LAYOUTS.iter().position(|known| *known == layout))
+        .map_or(UNKNOWN, |layout| layout as u8);
+    let grid =
+        |field, value| u32::try_from(value).map_err(|_| out_of_range(field, &world.identifier));
+
+    f.write_all(HEADER)?;
+    f.write_u32::<LittleEndian>(0)?; // flags
+    string::table(f, &strings)?; // identifiers and iids
+    f.write_u32::<LittleEndian>(identifier)?; // world name
+    f.write_u8(layout)?; // layout
+    f.write_u32::<LittleEndian>(grid("worldGridWidth", world.grid_width)?)?; // grid width
+    f.write_u32::<LittleEndian>(grid("worldGridHeight", world.grid_height)?)?; // grid height
+    f.write_u32::<LittleEndian>(u32::try_from(world.levels.len()).map_err(|_| too_large())?)?; // level count
+
+    for (level, (identifier, iid)) in world.levels.iter().zip(names) {
+        let int =
+            |field, value| i32::try_from(value).map_err(|_| out_of_range(field, &level.identifier));
+        let uint =
+            |field, value| u32::try_from(value).map_err(|_| out_of_range(field, &level.identifier));
+        let neighbours = level
+            .neighbours
+            .iter()
+            .filter_map(|neighbour| {
+                let index = world
+                    .levels
+                    .iter()
+                    .position(|other| other.iid == neighbour.level_iid)?;
+                let direction = DIRECTIONS
+                    .iter()
+                    .position(|known| *known == neighbour.dir)
+                    .map_or(UNKNOWN, |direction| direction as u8);
+                Some((direction, index as u32))
+            })
+            .collect::<Vec<_>>();
+
+        f.write_u32::<LittleEndian>(identifier)?; // identifier
+        f.write_u32::<LittleEndian>(iid)?; // iid
+        f.write_i32::<LittleEndian>(int("worldX", level.world_x)?)?; // world x
+        f.write_i32::<LittleEndian>(int("worldY", level.world_y)?)?; // world y
+        f.write_i32::<LittleEndian>(int("worldDepth", level.world_depth)?)?; // world depth
+        f.write_u32::<LittleEndian>(uint("pxWid", level.px_wid)?)?; // width
+        f.write_u32::<LittleEndian>(uint("pxHei", level.px_hei)?)?; // height
+        f.write_u16::<LittleEndian>(u16::try_from(neighbours.len()).map_err(|_| too_large())?)?; // neighbour count
+        for (direction, index) in neighbours {
+            f.write_u8(direction)?; // direction
+            f.write_u32::<LittleEndian>(index)?; // level index
+        }
+    }
+    Ok(())
+}
+
+/// Writes the map of every world of a project into `output_dir`, named after the world.
+/// Returns the path of each map written.
+pub fn run(project_path: &Path, output_dir: &Path) -> Result<Vec<PathBuf>> {
+    let raw = fs::read_to_string(project_path)
+        .with_context(|| format!("failed to read {}", project_path.display()))?;
+    let project: Project = compat::parse_project(&raw)?;
+
+    fs::create_dir_all(output_dir)
+        .with_context(|| format!("failed to create {}", output_dir.display()))?;
+
+    let mut paths = Vec::new();
+    for world in worlds(project_path, &project) {
+        let path = output_dir.join(format!("{}.{}", world.identifier, EXTENSION));
+        batch::write_atomic(&path, |mut dst| {
+            write(&mut dst, &world).with_context(|| format!("failed to write {}", path.display()))
+        })?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+impl WorldMap {
+    /// Reads a whole world map.
+    pub fn read<R: Read>(mut src: R) -> Result<Self, ReadError> {
+        let mut header = [0; HEADER.len()];
+        src.read_exact(&mut header)
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::UnexpectedEof => ReadError::BadHeader,
+                _ => ReadError::Io(err),
+            })?;
+        if header != HEADER {
+            return Err(ReadError::BadHeader);
+        }
+
+        let mut offset = HEADER.len() as u64;
+        let truncated = |offset| {
+            move |err: io::Error| match err.kind() {
+                io::ErrorKind::UnexpectedEof => ReadError::Truncated { offset },
+                _ => ReadError::Io(err),
+            }
+        };
+        let flags = src.read_u32::<LittleEndian>().map_err(truncated(offset))?;
+        if flags != 0 {
+            return Err(ReadError::UnsupportedFlags(flags));
+        }
+        offset += 4;
+
+        let code = src.read_u8().map_err(truncated(offset))?;
+        if code != 0x6 {
+            return Err(ReadError::UnknownCommand { code, offset });
+        }
+        let (strings, len) = reader::read_strings(&mut src, offset)?;
+        offset += 1 + len;
+        let string = |index: u32| {
+            strings
+                .get(index as usize)
+                .cloned()
+                .ok_or(ReadError::MissingString(index))
+        };
+
+        let f = &mut src;
+        let identifier = string(f.read_u32::<LittleEndian>().map_err(truncated(offset))?)?;
+        let layout = f.read_u8().map_err(truncated(offset))?;
+        let grid_width = f.read_u32::<LittleEndian>().map_err(truncated(offset))?;
+        let grid_height = f.read_u32::<LittleEndian>().map_err(truncated(offset))?;
+        let count = f.read_u32::<LittleEndian>().map_err(truncated(offset))?;
+        offset += 4 + 1 + 4 + 4 + 4;
+
+        let mut levels = Vec::new();
+        for _ in 0..count {
+            let identifier = f.read_u32::<LittleEndian>().map_err(truncated(offset))?;
+            let iid = f.read_u32::<LittleEndian>().map_err(truncated(offset))?;
+            let mut level = MapLevel {
+                identifier: string(identifier)?,
+                iid: string(iid)?,
+                world_x: f.read_i32::<LittleEndian>().map_err(truncated(offset))?,
+                world_y: f.read_i32::<LittleEndian>().map_err(truncated(offset))?,
+                world_depth: f.read_i32::<LittleEndian>().map_err(truncated(offset))?,
+                width: f.read_u32::<LittleEndian>().map_err(truncated(offset))?,
+                height: f.read_u32::<LittleEndian>().map_err(truncated(offset))?,
+                neighbours: Vec::new(),
+            };
+            let neighbours = f.read_u16::<LittleEndian>().map_err(truncated(offset))?;
+            for _ in 0..neighbours {
+                level.neighbours.push(Neighbour {
+                    direction: f.read_u8().map_err(truncated(offset))?,
+                    level: f.read_u32::<LittleEndian>().map_err(truncated(offset))?,
+                });
+            }
+            levels.push(level);
+            offset += 4 * 7 + 2 + 5 * u64::from(neighbours);
+        }
+
+        Ok(WorldMap {
+            identifier,
+            layout,
+            grid_width,
+            grid_height,
+            levels,
+        })
+    }
+
+    /// Looks a level up by identifier or iid.
+    pub fn find(&self, name: &str) -> Option<&MapLevel> {
+        self.levels
+            .iter()
+            .find(|level| level.identifier == name || level.iid == name)
+    }
+
+    /// The neighbour of a level in a direction, one of `DIRECTIONS`.
+    pub fn neighbour(&self, level: &MapLevel, direction: &str) -> Option<&MapLevel> {
+        let direction = DIRECTIONS.iter().position(|known| *known == direction)? as u8;
+        level
+            .neighbours
+            .iter()
+            .find(|neighbour| neighbour.direction == direction)
+            .and_then(|neighbour| self.levels.get(neighbour.level as usize))
+    }
+}
+
+/// LDtk name of a layout operand.
+pub fn layout_name(layout: u8) -> &'static str {
+    LAYOUTS.get(layout as usize).copied().unwrap_or("unknown")
+}
+
+/// LDtk name of a direction operand.
+pub fn direction_name(direction: u8) -> &'static str {
+    DIRECTIONS
+        .get(direction as usize)
+        .copied()
+        .unwrap_or("unknown")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::runtime::{fixtures, NeighbourLevel};
+
+    fn neighbour(dir: &str, level_iid: &str) -> NeighbourLevel {
+        NeighbourLevel {
+            dir: dir.into(),
+            level_iid: level_iid.into(),
+        }
+    }
+
+    #[test]
+    fn maps_read_back_with_their_neighbours() {
+        let mut project = fixtures::project(fixtures::defs(), Vec::new());
+        let mut east = project.levels[0].clone();
+        east.identifier = "Level_1".into();
+        east.iid = "level-1".into();
+        east.world_x = 64;
+        east.neighbours = vec![neighbour("w", "level-0")];
+        project.levels[0].neighbours = vec![
+            neighbour("e", "level-1"),
+            // levels of other worlds are left out
+            neighbour("n", "elsewhere"),
+        ];
+        project.levels.push(east);
+
+        let worlds = worlds(Path::new("maps/project.ldtk"), &project);
+        assert_eq!(worlds.len(), 1);
+        assert_eq!(worlds[0].identifier, "project");
+        let mut file = Vec::new();
+        write(&mut file, &worlds[0]).unwrap();
+
+        let map = WorldMap::read(Cursor::new(file)).unwrap();
+        assert_eq!(map.identifier, "project");
+        assert_eq!(layout_name(map.layout), "Free");
+        assert_eq!((map.grid_width, map.grid_height), (64, 64));
+
+        let west = map.find("Level_0").unwrap();
+        assert_eq!(west.neighbours.len(), 1);
+        let east = map.neighbour(west, "e").unwrap();
+        assert_eq!((east.iid.as_str(), east.world_x), ("level-1", 64));
+        assert_eq!(map.neighbour(east, "w").unwrap().identifier, "Level_0");
+        assert!(map.neighbour(west, "n").is_none());
+    }
+
+    #[test]
+    fn unknown_layouts_and_directions_are_kept_apart() {
+        assert_eq!(layout_name(UNKNOWN), "unknown");
+        assert_eq!(direction_name(UNKNOWN), "unknown");
+        assert_eq!(direction_name(3), "w");
+    }
+
+    #[test]
+    fn root_worlds_keep_the_whole_project_stem() {
+        let dir = std::env::temp_dir().join(format!("cartographer-world-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let project = dir.join("game.v2.ldtk");
+        let json = fixtures::project_json(
+            fixtures::defs(),
+            vec![fixtures::level("Level_0", 0, vec![])],
+        );
+        fs::write(&project, json.to_string()).unwrap();
+
+        let paths = run(&project, &dir.join("maps")).unwrap();
+        assert_eq!(paths, [dir.join("maps").join("game.v2.lvw")]);
+        let map = WorldMap::read(fs::File::open(&paths[0]).unwrap()).unwrap();
+        assert_eq!(map.identifier, "game.v2");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}